@@ -0,0 +1,134 @@
+use crate::config::{Dig, GameConfig, LockDelays, LockMode};
+use crate::global::*;
+use crate::mode::Mode;
+use crate::net::Network;
+use crate::randomizer::RandomizerKind;
+use crate::speed::{GravityCurve, LevelRule};
+use std::env;
+use std::path::PathBuf;
+
+/// Options changing the rules of the game.
+const RULE_OPTIONS: [&str; 11] = [
+    "--mode",
+    "--goal",
+    "--time",
+    "--dig",
+    "--messiness",
+    "--preview",
+    "--randomizer",
+    "--lock",
+    "--lock-delay",
+    "--gravity",
+    "--levels",
+];
+
+/// Options passed on the command line, e.g. `tetris --mode sprint --seed 42`.
+///
+/// `--mode` picks the standard rules of a mode and the other options adjust them. Playing
+/// over the network always uses the standard versus rules so both sides simulate alike.
+pub struct Args {
+    pub config: GameConfig,
+    /// Whether the rules were given, starting a game right away instead of at the title.
+    pub play: bool,
+    pub seed: Option<u64>,
+    pub network: Option<Network>,
+    /// Replay file to watch instead of playing.
+    pub replay: Option<PathBuf>,
+}
+
+impl Args {
+    pub fn parse() -> Self {
+        let options: Vec<(String, String)> = env::args()
+            .skip(1)
+            .collect::<Vec<_>>()
+            .chunks(2)
+            .map(|pair| (pair[0].clone(), pair.get(1).cloned().unwrap_or_default()))
+            .collect();
+
+        let mode = options
+            .iter()
+            .find(|(arg, _)| arg == "--mode")
+            .and_then(|(_, value)| Mode::from_name(value))
+            .unwrap_or(Mode::Marathon);
+        let mut args = Args {
+            config: mode.config(),
+            play: options
+                .iter()
+                .any(|(arg, _)| RULE_OPTIONS.contains(&arg.as_str())),
+            seed: None,
+            network: None,
+            replay: None,
+        };
+
+        for (arg, value) in options {
+            match arg.as_str() {
+                "--seed" => args.seed = value.parse().ok(),
+                "--host" => args.network = value.parse().ok().map(Network::Host),
+                "--connect" => args.network = Some(Network::Connect(value)),
+                "--replay" => args.replay = Some(PathBuf::from(value)),
+                "--goal" => {
+                    if let Ok(goal) = value.parse::<u32>() {
+                        args.config.line_goal = Some(goal.clamp(1, MAX_LINE_GOAL));
+                    }
+                }
+                "--time" => {
+                    if let Ok(seconds) = value.parse::<u64>() {
+                        let limit = seconds.saturating_mul(1000);
+                        args.config.time_limit = Some(limit.clamp(1000, MAX_TIME_LIMIT));
+                    }
+                }
+                "--dig" => {
+                    if let Ok(goal) = value.parse::<u32>() {
+                        let dig = args.config.dig.get_or_insert_with(Dig::default);
+                        dig.goal = goal.clamp(1, MAX_LINE_GOAL);
+                    }
+                }
+                "--messiness" => {
+                    if let Ok(messiness) = value.parse::<u32>() {
+                        let dig = args.config.dig.get_or_insert_with(Dig::default);
+                        dig.messiness = messiness.min(100);
+                    }
+                }
+                "--preview" => {
+                    if let Ok(count) = value.parse::<usize>() {
+                        if count > MAX_PREVIEW {
+                            eprintln!("--preview shows at most {} pieces.", MAX_PREVIEW);
+                        }
+                        args.config.preview_count = count.min(MAX_PREVIEW);
+                    }
+                }
+                "--randomizer" => {
+                    if let Some(kind) = RandomizerKind::from_name(&value) {
+                        args.config.randomizer = kind;
+                    }
+                }
+                "--lock" => {
+                    if let Some(mode) = LockMode::from_name(&value) {
+                        args.config.lock_mode = mode;
+                    }
+                }
+                "--lock-delay" => {
+                    if let Some(delays) = LockDelays::parse(&value) {
+                        args.config.lock_delays = delays;
+                    }
+                }
+                "--gravity" => {
+                    if let Some(curve) = GravityCurve::from_name(&value) {
+                        args.config.gravity = curve;
+                    }
+                }
+                "--levels" => {
+                    if let Some(rule) = LevelRule::from_name(&value) {
+                        args.config.level_rule = rule;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if args.network.is_some() {
+            args.config = Mode::Versus.config();
+        }
+        return args;
+    }
+}
@@ -1,3 +1,5 @@
+use crate::audio::SOUNDS;
+use macroquad::audio::{load_sound_from_bytes, Sound};
 use macroquad::prelude::*;
 use rust_embed::RustEmbed;
 
@@ -7,22 +9,41 @@ pub struct Asset;
 
 pub struct Assets {
     pub font: Font,
+    /// Sound effects in the order of `SOUNDS`.
+    pub sounds: Vec<Sound>,
+    pub music: Sound,
 }
 
 impl Assets {
     pub async fn new() -> Self {
         let font = Self::initialize_font().await;
-        Self { font }
+        let mut sounds = Vec::new();
+        for sound in SOUNDS {
+            sounds.push(Self::load_sound(sound.file()).await);
+        }
+        let music = Self::load_sound("sounds/music.wav").await;
+        Self {
+            font,
+            sounds,
+            music,
+        }
     }
 
     fn load_asset(path: &str) -> Vec<u8> {
         Asset::get(path)
             .map(|f| f.data.into())
-            .expect(&format!("File {} not found.", path))
+            .unwrap_or_else(|| panic!("File {} not found.", path))
     }
 
     async fn initialize_font() -> Font {
         let font_bytes = Self::load_asset("font.ttf");
         load_ttf_font_from_bytes(&font_bytes).expect("Failed to load font.")
     }
+
+    async fn load_sound(path: &str) -> Sound {
+        let sound_bytes = Self::load_asset(path);
+        load_sound_from_bytes(&sound_bytes)
+            .await
+            .expect("Failed to load sound.")
+    }
 }
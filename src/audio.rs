@@ -0,0 +1,165 @@
+use crate::assets::Assets;
+use crate::event::{Event, Spin};
+use macroquad::audio::{self, PlaySoundParams, Sound as Clip};
+
+/// A sound effect, played when the game sends the event it belongs to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Sound {
+    Move,
+    Rotate,
+    Lock,
+    HardDrop,
+    Hold,
+    Single,
+    Double,
+    Triple,
+    Tetris,
+    TSpin,
+    LevelUp,
+    GameOver,
+    Countdown,
+}
+
+pub const SOUNDS: [Sound; 13] = [
+    Sound::Move,
+    Sound::Rotate,
+    Sound::Lock,
+    Sound::HardDrop,
+    Sound::Hold,
+    Sound::Single,
+    Sound::Double,
+    Sound::Triple,
+    Sound::Tetris,
+    Sound::TSpin,
+    Sound::LevelUp,
+    Sound::GameOver,
+    Sound::Countdown,
+];
+
+impl Sound {
+    /// Path of the embedded WAV file.
+    pub fn file(self) -> &'static str {
+        match self {
+            Sound::Move => "sounds/move.wav",
+            Sound::Rotate => "sounds/rotate.wav",
+            Sound::Lock => "sounds/lock.wav",
+            Sound::HardDrop => "sounds/hard_drop.wav",
+            Sound::Hold => "sounds/hold.wav",
+            Sound::Single => "sounds/single.wav",
+            Sound::Double => "sounds/double.wav",
+            Sound::Triple => "sounds/triple.wav",
+            Sound::Tetris => "sounds/tetris.wav",
+            Sound::TSpin => "sounds/t_spin.wav",
+            Sound::LevelUp => "sounds/level_up.wav",
+            Sound::GameOver => "sounds/game_over.wav",
+            Sound::Countdown => "sounds/countdown.wav",
+        }
+    }
+
+    /// The sound `event` makes, if any.
+    pub fn of(event: &Event) -> Option<Self> {
+        match *event {
+            Event::Move => Some(Sound::Move),
+            Event::Rotate => Some(Sound::Rotate),
+            Event::Hold => Some(Sound::Hold),
+            Event::HardDrop { .. } => Some(Sound::HardDrop),
+            Event::Lock(_) => Some(Sound::Lock),
+            Event::Clear { spin, .. } if spin != Spin::None => Some(Sound::TSpin),
+            Event::Clear { lines, .. } => match lines {
+                1 => Some(Sound::Single),
+                2 => Some(Sound::Double),
+                3 => Some(Sound::Triple),
+                4 => Some(Sound::Tetris),
+                _ => None,
+            },
+            Event::LevelUp(_) => Some(Sound::LevelUp),
+            Event::Countdown(_) => Some(Sound::Countdown),
+            Event::GameOver(_) => Some(Sound::GameOver),
+            Event::Attack(_) | Event::Finished => None,
+        }
+    }
+}
+
+/// Sound effects and looping music.
+///
+/// The effects are played from the events of a game, so they follow replays just as they
+/// followed the game.
+pub struct Audio {
+    /// Sound effects in the order of `SOUNDS`.
+    sounds: Vec<Clip>,
+    music: Clip,
+    /// Volumes from 0 to 1, both 0 while muted.
+    sfx_volume: f32,
+    music_volume: f32,
+    /// Volume the music is playing at, which is 0 while it is paused.
+    playing_volume: f32,
+}
+
+impl Audio {
+    /// Starts the music straight away, silent until a game begins.
+    pub fn new(assets: &Assets) -> Self {
+        let params = PlaySoundParams {
+            looped: true,
+            volume: 0.0,
+        };
+        audio::play_sound(&assets.music, params);
+        return Audio {
+            sounds: assets.sounds.clone(),
+            music: assets.music.clone(),
+            sfx_volume: 0.0,
+            music_volume: 0.0,
+            playing_volume: 0.0,
+        };
+    }
+
+    /// Plays the sounds of the events of a tick or more, each sound once however often it
+    /// came up, as when a replay is fast-forwarded.
+    pub fn handle_events(&self, events: &[Event]) {
+        let mut sounds: Vec<Sound> = events.iter().filter_map(Sound::of).collect();
+        // A hard drop already lands with a thud of its own.
+        if sounds.contains(&Sound::HardDrop) {
+            sounds.retain(|&sound| sound != Sound::Lock);
+        }
+        for (i, sound) in sounds.iter().enumerate() {
+            if !sounds[..i].contains(sound) {
+                self.play(*sound);
+            }
+        }
+    }
+
+    pub fn play(&self, sound: Sound) {
+        if self.sfx_volume <= 0.0 {
+            return;
+        }
+        let index = SOUNDS.iter().position(|&known| known == sound).unwrap_or(0);
+        let params = PlaySoundParams {
+            looped: false,
+            volume: self.sfx_volume,
+        };
+        audio::play_sound(&self.sounds[index], params);
+    }
+
+    /// Plays or pauses the music, which resumes where it was paused.
+    ///
+    /// Sounds cannot be paused, so the music keeps going silently in the meantime.
+    pub fn set_music(&mut self, playing: bool) {
+        let volume = if playing { self.music_volume } else { 0.0 };
+        if volume != self.playing_volume {
+            audio::set_sound_volume(&self.music, volume);
+            self.playing_volume = volume;
+        }
+    }
+
+    /// Sets the volumes as percentages, silencing everything while `muted`.
+    pub fn set_volume(&mut self, sfx: u32, music: u32, muted: bool) {
+        let volume = |percent: u32| {
+            if muted {
+                0.0
+            } else {
+                percent.min(100) as f32 / 100.0
+            }
+        };
+        self.sfx_volume = volume(sfx);
+        self.music_volume = volume(music);
+    }
+}
@@ -0,0 +1,199 @@
+//! Compact binary encoding shared by replays and saved games.
+//!
+//! Unsigned numbers are LEB128 varints, signed ones zigzag encoded first, and floats
+//! their little-endian bits.
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// 64-bit FNV-1a hash of `bytes`, the same on every platform and build.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = FNV_OFFSET;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    return hash;
+}
+
+pub struct Writer(pub Vec<u8>);
+
+impl Writer {
+    pub fn new(magic: &[u8], version: u16) -> Self {
+        let mut bytes = magic.to_vec();
+        bytes.extend_from_slice(&version.to_le_bytes());
+        Writer(bytes)
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    pub fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    pub fn signed(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    pub fn float(&mut self, value: f32) {
+        self.0.extend_from_slice(&value.to_bits().to_le_bytes());
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.0.push(value as u8);
+    }
+
+    /// Writes 0 for `None` and the value plus one otherwise.
+    pub fn option(&mut self, value: Option<u64>) {
+        self.varint(value.map_or(0, |value| value + 1));
+    }
+
+    pub fn text(&mut self, text: &str) {
+        self.varint(text.len() as u64);
+        self.0.extend_from_slice(text.as_bytes());
+    }
+}
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Starts reading after the header, returning `None` unless it matches `magic` and
+    /// `version`.
+    pub fn new(bytes: &'a [u8], magic: &[u8], version: u16) -> Option<Self> {
+        let mut reader = Reader { bytes };
+        if reader.take(magic.len())? != magic
+            || u16::from_le_bytes(reader.take(2)?.try_into().ok()?) != version
+        {
+            return None;
+        }
+        return Some(reader);
+    }
+
+    pub fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < count {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        return Some(taken);
+    }
+
+    /// Everything not read yet.
+    pub fn rest(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        return Some(self.take(1)?[0]);
+    }
+
+    pub fn varint(&mut self) -> Option<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        return None;
+    }
+
+    pub fn signed(&mut self) -> Option<i64> {
+        let value = self.varint()?;
+        return Some((value >> 1) as i64 ^ -((value & 1) as i64));
+    }
+
+    pub fn float(&mut self) -> Option<f32> {
+        let bits = u32::from_le_bytes(self.take(4)?.try_into().ok()?);
+        return Some(f32::from_bits(bits));
+    }
+
+    pub fn bool(&mut self) -> Option<bool> {
+        match self.u8()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    pub fn option(&mut self) -> Option<Option<u64>> {
+        return Some(self.varint()?.checked_sub(1));
+    }
+
+    pub fn text(&mut self) -> Option<&'a str> {
+        let length = self.varint()? as usize;
+        return std::str::from_utf8(self.take(length)?).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_matches_reference_values() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let mut out = Writer::new(b"TEST", 3);
+        let numbers = [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX];
+        for &number in &numbers {
+            out.varint(number);
+        }
+        for signed in [0, -1, 1, i64::MIN, i64::MAX] {
+            out.signed(signed);
+        }
+        out.float(-1.5);
+        out.bool(true);
+        out.option(None);
+        out.option(Some(41));
+        out.text("tetris");
+
+        let mut reader = Reader::new(&out.0, b"TEST", 3).unwrap();
+        for &number in &numbers {
+            assert_eq!(reader.varint(), Some(number));
+        }
+        for signed in [0, -1, 1, i64::MIN, i64::MAX] {
+            assert_eq!(reader.signed(), Some(signed));
+        }
+        assert_eq!(reader.float(), Some(-1.5));
+        assert_eq!(reader.bool(), Some(true));
+        assert_eq!(reader.option(), Some(None));
+        assert_eq!(reader.option(), Some(Some(41)));
+        assert_eq!(reader.text(), Some("tetris"));
+        assert!(reader.rest().is_empty());
+        assert_eq!(reader.u8(), None);
+    }
+
+    #[test]
+    fn wrong_headers_are_rejected() {
+        let out = Writer::new(b"TEST", 3);
+        assert!(Reader::new(&out.0, b"TEST", 4).is_none());
+        assert!(Reader::new(&out.0, b"BEST", 3).is_none());
+        assert!(Reader::new(&out.0[..3], b"TEST", 3).is_none());
+    }
+
+    #[test]
+    fn malformed_values_are_rejected() {
+        // A varint whose continuation bits never end.
+        let mut reader = Reader { bytes: &[0xff; 11] };
+        assert_eq!(reader.varint(), None);
+        let mut reader = Reader { bytes: &[2] };
+        assert_eq!(reader.bool(), None);
+        let mut reader = Reader { bytes: &[10, b'a'] };
+        assert_eq!(reader.text(), None);
+    }
+}
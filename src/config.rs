@@ -0,0 +1,594 @@
+use crate::codec::{Reader, Writer};
+use crate::global::*;
+use crate::mode::Mode;
+use crate::randomizer::RandomizerKind;
+use crate::speed::{self, GravityCurve, LevelRule};
+
+/// How pieces respond to held keys, chosen by the player.
+#[derive(Clone, Copy, Debug)]
+pub struct Handling {
+    /// Delayed auto shift, in milliseconds before a held direction starts repeating.
+    pub das: u64,
+    /// Auto repeat rate, in milliseconds between repeated moves. 0 moves straight to the wall.
+    pub arr: u64,
+    /// Soft drop factor, how many times faster than gravity soft drop falls.
+    /// `INFINITE_SDF` drops straight to the floor.
+    pub sdf: u32,
+}
+
+impl Handling {
+    /// Whether the values are within what the settings allow.
+    pub fn is_valid(&self) -> bool {
+        self.das <= MAX_DAS && self.arr <= MAX_ARR && (1..=INFINITE_SDF).contains(&self.sdf)
+    }
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Handling {
+            das: 167,
+            arr: 33,
+            sdf: 20,
+        }
+    }
+}
+
+/// Pauses after a piece locks, letting the effects play out before the next piece spawns.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Delays {
+    /// Milliseconds before every new piece spawns, known as ARE.
+    pub entry: u64,
+    /// Further milliseconds spent on placements that clear lines.
+    pub line_clear: u64,
+}
+
+impl Delays {
+    /// Long enough for the cleared rows to flash and collapse before play goes on.
+    pub const ANIMATED: Delays = Delays {
+        entry: 100,
+        line_clear: 300,
+    };
+}
+
+/// When a piece resting on the stack locks.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LockMode {
+    /// Moves and rotations on the stack restart the lock delay up to `LOCK_RESET_LIMIT`
+    /// times, the count starting over whenever the piece reaches a new lowest row.
+    Extended,
+    /// Every move and rotation restarts the lock delay.
+    Infinite,
+    /// Only falling a row restarts the lock delay.
+    Classic,
+}
+
+impl LockMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            LockMode::Extended => "extended",
+            LockMode::Infinite => "infinite",
+            LockMode::Classic => "classic",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "extended" => Some(LockMode::Extended),
+            "infinite" => Some(LockMode::Infinite),
+            "classic" => Some(LockMode::Classic),
+            _ => None,
+        }
+    }
+}
+
+/// Lock delay in milliseconds by level, as up to `MAX_LOCK_STEPS` `(from_level, delay)`
+/// steps sorted by level.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LockDelays {
+    steps: [(u32, u64); MAX_LOCK_STEPS],
+    count: usize,
+}
+
+impl LockDelays {
+    /// Half a second at first, shortening as gravity nears 20G and again through the
+    /// hundreds of levels of the per-piece rule.
+    pub const LEVELS: LockDelays = LockDelays::table(&[
+        (0, 500),
+        (10, 450),
+        (15, 400),
+        (20, 350),
+        (100, 300),
+        (300, 250),
+        (500, 200),
+    ]);
+
+    const fn table(table: &[(u32, u64)]) -> Self {
+        let mut steps = [(0, 0); MAX_LOCK_STEPS];
+        let mut i = 0;
+        while i < table.len() {
+            steps[i] = table[i];
+            i += 1;
+        }
+        return LockDelays {
+            steps,
+            count: table.len(),
+        };
+    }
+
+    /// A table of steps, or `None` if there are more than `MAX_LOCK_STEPS`.
+    pub fn new(table: &[(u32, u64)]) -> Option<Self> {
+        if table.len() > MAX_LOCK_STEPS {
+            return None;
+        }
+        return Some(LockDelays::table(table));
+    }
+
+    /// Reads a table written as `500,20:400,30:300`, a delay in milliseconds from level 0
+    /// followed by `level:delay` steps. Returns `None` unless the table is valid.
+    pub fn parse(text: &str) -> Option<Self> {
+        let steps = text
+            .split(',')
+            .enumerate()
+            .map(|(i, step)| match step.split_once(':') {
+                Some((level, delay)) => {
+                    Some((level.trim().parse().ok()?, delay.trim().parse().ok()?))
+                }
+                None if i == 0 => Some((0, step.trim().parse().ok()?)),
+                None => None,
+            })
+            .collect::<Option<Vec<(u32, u64)>>>()?;
+        return LockDelays::new(&steps).filter(LockDelays::is_valid);
+    }
+
+    pub fn steps(&self) -> &[(u32, u64)] {
+        &self.steps[..self.count]
+    }
+
+    pub fn at(&self, level: u32) -> u64 {
+        speed::step(self.steps(), level)
+    }
+
+    /// Whether the table starts at level 0, climbs in level and only holds delays the game
+    /// can be played with.
+    pub fn is_valid(&self) -> bool {
+        let steps = self.steps();
+        steps.first().is_some_and(|&(from, _)| from == 0)
+            && steps.windows(2).all(|pair| pair[0].0 < pair[1].0)
+            && steps
+                .iter()
+                .all(|&(_, delay)| (1..=MAX_LOCK_DELAY).contains(&delay))
+    }
+}
+
+/// Garbage the player digs through, kept topped up until `goal` rows have been cleared.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Dig {
+    pub goal: u32,
+    /// Garbage rows kept on the board while more remain.
+    pub height: usize,
+    /// Chance in percent that the hole moves between rows.
+    pub messiness: u32,
+}
+
+impl Default for Dig {
+    fn default() -> Self {
+        Dig {
+            goal: 10,
+            height: 10,
+            messiness: 100,
+        }
+    }
+}
+
+/// Rules a game is played with, picked by the mode before the game starts.
+#[derive(Clone, Copy, Debug)]
+pub struct GameConfig {
+    pub mode: Mode,
+    pub randomizer: RandomizerKind,
+    pub preview_count: usize,
+    pub handling: Handling,
+    pub lock_mode: LockMode,
+    pub lock_delays: LockDelays,
+    pub gravity: GravityCurve,
+    pub level_rule: LevelRule,
+    /// Lines that finish the game, if it ends on a line count.
+    pub line_goal: Option<u32>,
+    /// Milliseconds after which the game ends, if it is played against the clock.
+    pub time_limit: Option<u64>,
+    pub dig: Option<Dig>,
+    pub delays: Delays,
+}
+
+impl GameConfig {
+    /// Whether the game ends on reaching a goal rather than running until it is lost.
+    pub fn has_goal(&self) -> bool {
+        self.line_goal.is_some() || self.dig.is_some()
+    }
+
+    /// Whether the rules are the standard ones of the mode. Only the handling of the player
+    /// and the delays, which are there for the effects, may differ.
+    pub fn is_standard(&self) -> bool {
+        let rules = self.mode.config();
+        return self.randomizer == rules.randomizer
+            && self.preview_count == rules.preview_count
+            && self.lock_mode == rules.lock_mode
+            && self.lock_delays == rules.lock_delays
+            && self.gravity == rules.gravity
+            && self.level_rule == rules.level_rule
+            && self.line_goal == rules.line_goal
+            && self.time_limit == rules.time_limit
+            && self.dig == rules.dig;
+    }
+
+    /// Whether every rule is within the range the game is played with. Rules read from
+    /// files or the network may be anything, and a level of no lines or a queue of
+    /// millions of pieces would hang the game.
+    pub fn is_valid(&self) -> bool {
+        let level_rule = match self.level_rule {
+            LevelRule::FixedLines(lines) => (1..=MAX_LEVEL_LINES).contains(&lines),
+            LevelRule::Fixed | LevelRule::VariableGoal | LevelRule::PerPiece => true,
+        };
+        let dig = self.dig.is_none_or(|dig| {
+            (1..=MAX_LINE_GOAL).contains(&dig.goal)
+                && (1..=MAX_DIG_HEIGHT).contains(&dig.height)
+                && dig.messiness <= 100
+        });
+        return self.handling.is_valid()
+            && self.preview_count <= MAX_PREVIEW
+            && self.lock_delays.is_valid()
+            && level_rule
+            && self
+                .line_goal
+                .is_none_or(|goal| (1..=MAX_LINE_GOAL).contains(&goal))
+            && self
+                .time_limit
+                .is_none_or(|limit| (1..=MAX_TIME_LIMIT).contains(&limit))
+            && dig
+            && self.delays.entry <= MAX_DELAY
+            && self.delays.line_clear <= MAX_DELAY;
+    }
+
+    /// Writes the rules for replays and saved games.
+    pub fn encode(&self, out: &mut Writer) {
+        out.text(self.mode.name());
+        out.text(self.randomizer.name());
+        out.varint(self.preview_count as u64);
+        out.varint(self.handling.das);
+        out.varint(self.handling.arr);
+        out.varint(self.handling.sdf as u64);
+        out.text(self.lock_mode.name());
+        out.varint(self.lock_delays.count as u64);
+        for &(from, delay) in self.lock_delays.steps() {
+            out.varint(from as u64);
+            out.varint(delay);
+        }
+        out.text(self.gravity.name());
+        match self.level_rule {
+            LevelRule::Fixed => out.varint(0),
+            LevelRule::FixedLines(lines) => {
+                out.varint(1);
+                out.varint(lines as u64);
+            }
+            LevelRule::VariableGoal => out.varint(2),
+            LevelRule::PerPiece => out.varint(3),
+        }
+        out.option(self.line_goal.map(u64::from));
+        out.option(self.time_limit);
+        out.option(self.dig.map(|dig| dig.goal as u64));
+        if let Some(dig) = self.dig {
+            out.varint(dig.height as u64);
+            out.varint(dig.messiness as u64);
+        }
+        out.varint(self.delays.entry);
+        out.varint(self.delays.line_clear);
+    }
+
+    /// Reads rules written by `encode`, refusing any out of range.
+    pub fn decode(reader: &mut Reader) -> Option<Self> {
+        let mode = Mode::from_name(reader.text()?)?;
+        let mut config = GameConfig {
+            mode,
+            randomizer: RandomizerKind::from_name(reader.text()?)?,
+            preview_count: usize::try_from(reader.varint()?).ok()?,
+            handling: Handling {
+                das: reader.varint()?,
+                arr: reader.varint()?,
+                sdf: u32::try_from(reader.varint()?).ok()?,
+            },
+            lock_mode: LockMode::from_name(reader.text()?)?,
+            lock_delays: {
+                let count = usize::try_from(reader.varint()?).ok()?;
+                if count > MAX_LOCK_STEPS {
+                    return None;
+                }
+                let mut steps = Vec::with_capacity(count);
+                for _ in 0..count {
+                    steps.push((u32::try_from(reader.varint()?).ok()?, reader.varint()?));
+                }
+                LockDelays::new(&steps)?
+            },
+            gravity: GravityCurve::from_name(reader.text()?)?,
+            level_rule: match reader.varint()? {
+                0 => LevelRule::Fixed,
+                1 => LevelRule::FixedLines(u32::try_from(reader.varint()?).ok()?),
+                2 => LevelRule::VariableGoal,
+                3 => LevelRule::PerPiece,
+                _ => return None,
+            },
+            line_goal: match reader.option()? {
+                Some(goal) => Some(u32::try_from(goal).ok()?),
+                None => None,
+            },
+            time_limit: reader.option()?,
+            ..mode.config()
+        };
+        config.dig = match reader.option()? {
+            Some(goal) => Some(Dig {
+                goal: u32::try_from(goal).ok()?,
+                height: usize::try_from(reader.varint()?).ok()?,
+                messiness: u32::try_from(reader.varint()?).ok()?,
+            }),
+            None => None,
+        };
+        config.delays = Delays {
+            entry: reader.varint()?,
+            line_clear: reader.varint()?,
+        };
+        return config.is_valid().then_some(config);
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            mode: Mode::Marathon,
+            randomizer: RandomizerKind::Bag7,
+            preview_count: 5,
+            handling: Handling::default(),
+            lock_mode: LockMode::Extended,
+            lock_delays: LockDelays::LEVELS,
+            gravity: GravityCurve::Guideline,
+            level_rule: LevelRule::FixedLines(10),
+            line_goal: None,
+            time_limit: None,
+            dig: None,
+            delays: Delays::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::MODES;
+
+    fn encode(config: &GameConfig) -> Vec<u8> {
+        let mut out = Writer::new(b"TEST", 1);
+        config.encode(&mut out);
+        return out.0;
+    }
+
+    fn decode(bytes: &[u8]) -> Option<GameConfig> {
+        return GameConfig::decode(&mut Reader::new(bytes, b"TEST", 1)?);
+    }
+
+    #[test]
+    fn standard_rules_round_trip() {
+        for mode in MODES {
+            let config = GameConfig {
+                delays: Delays::ANIMATED,
+                ..mode.config()
+            };
+            assert!(config.is_valid(), "{:?}", mode);
+            let bytes = encode(&config);
+            assert_eq!(encode(&decode(&bytes).unwrap()), bytes, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn only_handling_and_delays_keep_rules_standard() {
+        let standard = Mode::Sprint.config();
+        assert!(standard.is_standard());
+        assert!(GameConfig {
+            handling: Handling {
+                das: 100,
+                arr: 0,
+                sdf: INFINITE_SDF,
+            },
+            delays: Delays::ANIMATED,
+            ..standard
+        }
+        .is_standard());
+
+        let changed = [
+            GameConfig {
+                randomizer: RandomizerKind::Random,
+                ..standard
+            },
+            GameConfig {
+                preview_count: 1,
+                ..standard
+            },
+            GameConfig {
+                lock_mode: LockMode::Infinite,
+                ..standard
+            },
+            GameConfig {
+                level_rule: LevelRule::FixedLines(1),
+                ..standard
+            },
+            GameConfig {
+                line_goal: Some(20),
+                ..standard
+            },
+        ];
+        for config in changed {
+            assert!(!config.is_standard(), "{:?}", config);
+        }
+    }
+
+    #[test]
+    fn lock_delay_tables_are_parsed() {
+        let table = LockDelays::parse("500,20:400, 30:300").unwrap();
+        assert_eq!(table.steps(), [(0, 500), (20, 400), (30, 300)]);
+        assert_eq!(
+            LockDelays::parse("250"),
+            Some(LockDelays::new(&[(0, 250)]).unwrap())
+        );
+        assert_eq!(LockDelays::parse("0:250,5:200").unwrap().at(7), 200);
+
+        let invalid = [
+            "",
+            "fast",
+            "500,400",
+            "500,20:",
+            "10:500",
+            "500,30:400,20:300",
+            "500,20:0",
+            "500,20:9999",
+            "500,1:1,2:1,3:1,4:1,5:1,6:1,7:1,8:1",
+        ];
+        for text in invalid {
+            assert_eq!(LockDelays::parse(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn lock_delays_round_trip() {
+        let config = GameConfig {
+            lock_delays: LockDelays::new(&[(0, 500), (100, 300), (500, 150)]).unwrap(),
+            ..GameConfig::default()
+        };
+        assert!(config.is_valid());
+        let decoded = decode(&encode(&config)).unwrap();
+        assert_eq!(decoded.lock_delays, config.lock_delays);
+        assert_eq!(decoded.lock_delays.at(99), 500);
+        assert_eq!(decoded.lock_delays.at(100), 300);
+        assert_eq!(decoded.lock_delays.at(999), 150);
+        assert!(!decoded.is_standard());
+    }
+
+    #[test]
+    fn truncated_rules_are_rejected() {
+        let bytes = encode(&Mode::Dig.config());
+        for length in 0..bytes.len() {
+            assert!(decode(&bytes[..length]).is_none(), "{} bytes", length);
+        }
+    }
+
+    #[test]
+    fn out_of_range_rules_are_rejected() {
+        let standard = GameConfig::default();
+        let dig = Mode::Dig.config().dig.unwrap();
+        assert!(GameConfig {
+            preview_count: MAX_PREVIEW,
+            ..standard
+        }
+        .is_valid());
+        let invalid = [
+            GameConfig {
+                level_rule: LevelRule::FixedLines(0),
+                ..standard
+            },
+            GameConfig {
+                preview_count: 1 << 40,
+                ..standard
+            },
+            GameConfig {
+                preview_count: MAX_PREVIEW + 1,
+                ..standard
+            },
+            GameConfig {
+                line_goal: Some(0),
+                ..standard
+            },
+            GameConfig {
+                line_goal: Some(u32::MAX),
+                ..standard
+            },
+            GameConfig {
+                time_limit: Some(MAX_TIME_LIMIT + 1),
+                ..standard
+            },
+            GameConfig {
+                dig: Some(Dig {
+                    height: 1 << 40,
+                    ..dig
+                }),
+                ..standard
+            },
+            GameConfig {
+                dig: Some(Dig {
+                    goal: u32::MAX,
+                    ..dig
+                }),
+                ..standard
+            },
+            GameConfig {
+                dig: Some(Dig {
+                    messiness: 101,
+                    ..dig
+                }),
+                ..standard
+            },
+            GameConfig {
+                handling: Handling {
+                    sdf: 0,
+                    ..standard.handling
+                },
+                ..standard
+            },
+            GameConfig {
+                handling: Handling {
+                    das: u64::MAX,
+                    ..standard.handling
+                },
+                ..standard
+            },
+            GameConfig {
+                lock_delays: LockDelays::new(&[(0, 0)]).unwrap(),
+                ..standard
+            },
+            GameConfig {
+                lock_delays: LockDelays::new(&[(0, MAX_LOCK_DELAY + 1)]).unwrap(),
+                ..standard
+            },
+            GameConfig {
+                lock_delays: LockDelays::new(&[]).unwrap(),
+                ..standard
+            },
+            GameConfig {
+                lock_delays: LockDelays::new(&[(10, 500)]).unwrap(),
+                ..standard
+            },
+            GameConfig {
+                lock_delays: LockDelays::new(&[(0, 500), (20, 400), (20, 300)]).unwrap(),
+                ..standard
+            },
+            GameConfig {
+                delays: Delays {
+                    entry: 0,
+                    line_clear: u64::MAX,
+                },
+                ..standard
+            },
+        ];
+        for config in invalid {
+            assert!(!config.is_valid(), "{:?}", config);
+            assert!(decode(&encode(&config)).is_none(), "{:?}", config);
+        }
+    }
+
+    #[test]
+    fn unknown_names_are_rejected() {
+        let mut out = Writer::new(b"TEST", 1);
+        out.text("tetris 99");
+        assert!(decode(&out.0).is_none());
+
+        let mut bytes = encode(&GameConfig::default());
+        // The mode name is first, so the randomizer name follows it.
+        let at = 1 + "marathon".len() + 1;
+        bytes[at] = b'x';
+        assert!(decode(&bytes).is_none());
+    }
+}
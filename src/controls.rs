@@ -0,0 +1,305 @@
+use crate::gamepad::Button;
+use crate::input::Action;
+use macroquad::prelude::*;
+
+/// Something a control can be bound to: a key, or a button of a controller.
+pub trait Binding: Copy + PartialEq {
+    /// Name in the settings file.
+    fn name(self) -> Option<&'static str>;
+    fn from_name(name: &str) -> Option<Self>;
+    /// Short name as shown to the player.
+    fn label(self) -> String;
+}
+
+impl Binding for KeyCode {
+    fn name(self) -> Option<&'static str> {
+        key_name(self)
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        key_from_name(name)
+    }
+
+    fn label(self) -> String {
+        key_label(self)
+    }
+}
+
+impl Binding for Button {
+    fn name(self) -> Option<&'static str> {
+        Some(Button::name(self))
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Button::from_name(name)
+    }
+
+    fn label(self) -> String {
+        Button::label(self).to_owned()
+    }
+}
+
+/// Keys or buttons bound to every action, each action accepting any number of them.
+#[derive(Clone, Debug)]
+pub struct Controls<B = KeyCode> {
+    pub left: Vec<B>,
+    pub right: Vec<B>,
+    pub soft_drop: Vec<B>,
+    pub hard_drop: Vec<B>,
+    pub rotate_clockwise: Vec<B>,
+    pub rotate_counterclockwise: Vec<B>,
+    pub hold: Vec<B>,
+    pub pause: Vec<B>,
+}
+
+/// Rows of the bindings screen and settings file, in display order.
+pub const CONTROL_COUNT: usize = 8;
+
+impl Controls {
+    pub fn player1() -> Self {
+        Controls {
+            left: vec![KeyCode::Left],
+            right: vec![KeyCode::Right],
+            soft_drop: vec![KeyCode::Down],
+            hard_drop: vec![KeyCode::Space],
+            rotate_clockwise: vec![KeyCode::Up],
+            rotate_counterclockwise: vec![KeyCode::LeftControl],
+            hold: vec![KeyCode::C],
+            pause: vec![KeyCode::Escape],
+        }
+    }
+
+    pub fn player2() -> Self {
+        Controls {
+            left: vec![KeyCode::A],
+            right: vec![KeyCode::D],
+            soft_drop: vec![KeyCode::S],
+            hard_drop: vec![KeyCode::W],
+            rotate_clockwise: vec![KeyCode::E],
+            rotate_counterclockwise: vec![KeyCode::Q],
+            hold: vec![KeyCode::LeftShift],
+            pause: vec![KeyCode::Tab],
+        }
+    }
+
+    /// The default bindings of player `player`, counting from 0.
+    pub fn defaults(player: usize) -> Self {
+        if player == 0 {
+            return Controls::player1();
+        }
+        return Controls::player2();
+    }
+}
+
+impl Controls<Button> {
+    /// Bindings of a controller not set up yet.
+    pub fn gamepad() -> Self {
+        Controls {
+            left: vec![Button::DpadLeft],
+            right: vec![Button::DpadRight],
+            soft_drop: vec![Button::DpadDown],
+            hard_drop: vec![Button::DpadUp],
+            rotate_clockwise: vec![Button::South, Button::North],
+            rotate_counterclockwise: vec![Button::East, Button::West],
+            hold: vec![Button::LeftShoulder, Button::RightShoulder],
+            pause: vec![Button::Start],
+        }
+    }
+}
+
+impl<B: Binding> Controls<B> {
+    pub fn keys(&self, action: Action) -> &[B] {
+        match action {
+            Action::Left => &self.left,
+            Action::Right => &self.right,
+            Action::SoftDrop => &self.soft_drop,
+            Action::HardDrop => &self.hard_drop,
+            Action::RotateClockwise => &self.rotate_clockwise,
+            Action::RotateCounterclockwise => &self.rotate_counterclockwise,
+            Action::Hold => &self.hold,
+        }
+    }
+
+    /// The setting name, help label and keys of the control in row `index`.
+    pub fn entry(&self, index: usize) -> (&'static str, &'static str, &[B]) {
+        match index {
+            0 => ("left", "Move Left", &self.left),
+            1 => ("right", "Move Right", &self.right),
+            2 => ("soft_drop", "Soft Drop", &self.soft_drop),
+            3 => ("hard_drop", "Hard Drop", &self.hard_drop),
+            4 => (
+                "rotate_clockwise",
+                "Rotate Clockwise",
+                &self.rotate_clockwise,
+            ),
+            5 => (
+                "rotate_counterclockwise",
+                "Rotate Counterclockwise",
+                &self.rotate_counterclockwise,
+            ),
+            6 => ("hold", "Hold", &self.hold),
+            _ => ("pause", "Pause", &self.pause),
+        }
+    }
+
+    pub fn entry_mut(&mut self, index: usize) -> &mut Vec<B> {
+        match index {
+            0 => &mut self.left,
+            1 => &mut self.right,
+            2 => &mut self.soft_drop,
+            3 => &mut self.hard_drop,
+            4 => &mut self.rotate_clockwise,
+            5 => &mut self.rotate_counterclockwise,
+            6 => &mut self.hold,
+            _ => &mut self.pause,
+        }
+    }
+
+    /// Binds `key` to the control in row `index`, taking it away from any other control.
+    pub fn bind(&mut self, index: usize, key: B) {
+        for i in 0..CONTROL_COUNT {
+            self.entry_mut(i).retain(|&bound| bound != key);
+        }
+        self.entry_mut(index).push(key);
+    }
+
+    /// A line such as `← / A - Move Left` for the help overlay.
+    pub fn help_line(&self, index: usize) -> String {
+        let (_, label, keys) = self.entry(index);
+        if keys.is_empty() {
+            return format!("(unbound) - {}", label);
+        }
+        let keys: Vec<String> = keys.iter().map(|&key| key.label()).collect();
+        return format!("{} - {}", keys.join(" / "), label);
+    }
+}
+
+/// Short name of `key` as shown to the player.
+pub fn key_label(key: KeyCode) -> String {
+    let label = match key {
+        KeyCode::Up => "↑",
+        KeyCode::Down => "↓",
+        KeyCode::Left => "←",
+        KeyCode::Right => "→",
+        KeyCode::LeftControl | KeyCode::RightControl => "Ctrl",
+        KeyCode::LeftShift | KeyCode::RightShift => "Shift",
+        KeyCode::LeftAlt | KeyCode::RightAlt => "Alt",
+        _ => key_name(key).unwrap_or("?").trim_start_matches("Key"),
+    };
+    return label.to_owned();
+}
+
+/// Name of `key` in the settings file.
+pub fn key_name(key: KeyCode) -> Option<&'static str> {
+    KEY_NAMES
+        .iter()
+        .find(|(code, _)| *code == key)
+        .map(|(_, name)| *name)
+}
+
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    KEY_NAMES
+        .iter()
+        .find(|(_, key_name)| *key_name == name)
+        .map(|(code, _)| *code)
+}
+
+const KEY_NAMES: [(KeyCode, &str); 97] = [
+    (KeyCode::Space, "Space"),
+    (KeyCode::Apostrophe, "Apostrophe"),
+    (KeyCode::Comma, "Comma"),
+    (KeyCode::Minus, "Minus"),
+    (KeyCode::Period, "Period"),
+    (KeyCode::Slash, "Slash"),
+    (KeyCode::Key0, "Key0"),
+    (KeyCode::Key1, "Key1"),
+    (KeyCode::Key2, "Key2"),
+    (KeyCode::Key3, "Key3"),
+    (KeyCode::Key4, "Key4"),
+    (KeyCode::Key5, "Key5"),
+    (KeyCode::Key6, "Key6"),
+    (KeyCode::Key7, "Key7"),
+    (KeyCode::Key8, "Key8"),
+    (KeyCode::Key9, "Key9"),
+    (KeyCode::Semicolon, "Semicolon"),
+    (KeyCode::Equal, "Equal"),
+    (KeyCode::A, "A"),
+    (KeyCode::B, "B"),
+    (KeyCode::C, "C"),
+    (KeyCode::D, "D"),
+    (KeyCode::E, "E"),
+    (KeyCode::F, "F"),
+    (KeyCode::G, "G"),
+    (KeyCode::H, "H"),
+    (KeyCode::I, "I"),
+    (KeyCode::J, "J"),
+    (KeyCode::K, "K"),
+    (KeyCode::L, "L"),
+    (KeyCode::M, "M"),
+    (KeyCode::N, "N"),
+    (KeyCode::O, "O"),
+    (KeyCode::P, "P"),
+    (KeyCode::Q, "Q"),
+    (KeyCode::R, "R"),
+    (KeyCode::S, "S"),
+    (KeyCode::T, "T"),
+    (KeyCode::U, "U"),
+    (KeyCode::V, "V"),
+    (KeyCode::W, "W"),
+    (KeyCode::X, "X"),
+    (KeyCode::Y, "Y"),
+    (KeyCode::Z, "Z"),
+    (KeyCode::LeftBracket, "LeftBracket"),
+    (KeyCode::Backslash, "Backslash"),
+    (KeyCode::RightBracket, "RightBracket"),
+    (KeyCode::GraveAccent, "GraveAccent"),
+    (KeyCode::Escape, "Escape"),
+    (KeyCode::Enter, "Enter"),
+    (KeyCode::Tab, "Tab"),
+    (KeyCode::Backspace, "Backspace"),
+    (KeyCode::Insert, "Insert"),
+    (KeyCode::Delete, "Delete"),
+    (KeyCode::Right, "Right"),
+    (KeyCode::Left, "Left"),
+    (KeyCode::Down, "Down"),
+    (KeyCode::Up, "Up"),
+    (KeyCode::PageUp, "PageUp"),
+    (KeyCode::PageDown, "PageDown"),
+    (KeyCode::Home, "Home"),
+    (KeyCode::End, "End"),
+    (KeyCode::CapsLock, "CapsLock"),
+    (KeyCode::F1, "F1"),
+    (KeyCode::F2, "F2"),
+    (KeyCode::F3, "F3"),
+    (KeyCode::F4, "F4"),
+    (KeyCode::F5, "F5"),
+    (KeyCode::F6, "F6"),
+    (KeyCode::F7, "F7"),
+    (KeyCode::F8, "F8"),
+    (KeyCode::F9, "F9"),
+    (KeyCode::F10, "F10"),
+    (KeyCode::F11, "F11"),
+    (KeyCode::F12, "F12"),
+    (KeyCode::Kp0, "Kp0"),
+    (KeyCode::Kp1, "Kp1"),
+    (KeyCode::Kp2, "Kp2"),
+    (KeyCode::Kp3, "Kp3"),
+    (KeyCode::Kp4, "Kp4"),
+    (KeyCode::Kp5, "Kp5"),
+    (KeyCode::Kp6, "Kp6"),
+    (KeyCode::Kp7, "Kp7"),
+    (KeyCode::Kp8, "Kp8"),
+    (KeyCode::Kp9, "Kp9"),
+    (KeyCode::KpDecimal, "KpDecimal"),
+    (KeyCode::KpDivide, "KpDivide"),
+    (KeyCode::KpMultiply, "KpMultiply"),
+    (KeyCode::KpSubtract, "KpSubtract"),
+    (KeyCode::KpAdd, "KpAdd"),
+    (KeyCode::KpEnter, "KpEnter"),
+    (KeyCode::LeftShift, "LeftShift"),
+    (KeyCode::LeftControl, "LeftControl"),
+    (KeyCode::LeftAlt, "LeftAlt"),
+    (KeyCode::RightShift, "RightShift"),
+    (KeyCode::RightControl, "RightControl"),
+    (KeyCode::RightAlt, "RightAlt"),
+];
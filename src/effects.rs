@@ -0,0 +1,235 @@
+use crate::event::{Event, Placement, Spin};
+use crate::global::*;
+use macroquad::prelude::*;
+
+/// A block thrown off by a T-spin or perfect clear, falling as it fades.
+pub struct Particle {
+    /// Grid position at `start`.
+    origin: Vec2,
+    /// Blocks per second.
+    velocity: Vec2,
+    /// Piece whose color the particle takes, in the order of `SHAPES`.
+    pub piece: usize,
+    start: f64,
+}
+
+impl Particle {
+    pub fn pos(&self, now: f64) -> Vec2 {
+        let t = (now - self.start) as f32;
+        return self.origin + self.velocity * t + Vec2::new(0.0, PARTICLE_GRAVITY) * t * t * 0.5;
+    }
+
+    pub fn alpha(&self, now: f64) -> f32 {
+        return fade(self.start, PARTICLE_DURATION, now);
+    }
+}
+
+/// A hard-dropped piece leaving a streak behind it.
+pub struct Trail {
+    pub piece: Placement,
+    pub rows: u32,
+    start: f64,
+}
+
+impl Trail {
+    pub fn alpha(&self, now: f64) -> f32 {
+        return fade(self.start, TRAIL_DURATION, now);
+    }
+}
+
+/// Animations started by the events of a game, timed with `get_time`.
+///
+/// Rows are counted on the board as it is after the latest clear, the effects working out
+/// where things were from the events alone.
+#[derive(Default)]
+pub struct Effects {
+    /// Rows cleared last, one bit per row as before the clear, and when.
+    clear: Option<(u32, f64)>,
+    /// Blocks of recently locked pieces, where they rest once cleared rows are gone.
+    locks: Vec<(Vec<(i32, i32)>, f64)>,
+    pub trails: Vec<Trail>,
+    pub particles: Vec<Particle>,
+    shake: Option<f64>,
+}
+
+impl Effects {
+    pub fn handle(&mut self, event: &Event) {
+        let now = get_time();
+        match *event {
+            Event::HardDrop { piece, rows } if rows > 0 => self.trails.push(Trail {
+                piece,
+                rows,
+                start: now,
+            }),
+            Event::Lock(piece) => self.locks.push((piece.cells().collect(), now)),
+            Event::Clear {
+                lines,
+                rows,
+                spin,
+                perfect_clear,
+                ..
+            } => {
+                // Thrown from where the blocks were, while the rows flash.
+                if perfect_clear {
+                    self.burst_rows(rows, now);
+                } else if spin != Spin::None {
+                    self.burst_piece(now);
+                }
+                if lines > 0 {
+                    self.clear = Some((rows, now));
+                    // The piece that completed the rows locked in the same tick.
+                    if let Some((cells, _)) = self.locks.last_mut() {
+                        *cells = cells
+                            .iter()
+                            .filter(|&&(_, y)| rows & row_bit(y) == 0)
+                            .map(|&(x, y)| (x, y + cleared_below(rows, y)))
+                            .collect();
+                    }
+                }
+                if lines == 4 {
+                    self.shake = Some(now);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Forgets the effects that have played out.
+    pub fn expire(&mut self) {
+        let now = get_time();
+        let playing = |start: f64, duration: f64| now - start < duration;
+        self.locks
+            .retain(|&(_, start)| playing(start, LOCK_FLASH_DURATION));
+        self.trails
+            .retain(|trail| playing(trail.start, TRAIL_DURATION));
+        self.particles
+            .retain(|particle| playing(particle.start, PARTICLE_DURATION));
+        if self
+            .clear
+            .is_some_and(|(_, start)| !playing(start, ROW_FLASH_DURATION + COLLAPSE_DURATION))
+        {
+            self.clear = None;
+        }
+        if self
+            .shake
+            .is_some_and(|start| !playing(start, SHAKE_DURATION))
+        {
+            self.shake = None;
+        }
+    }
+
+    /// Cleared rows to flash, one bit per row as before the clear, and how bright.
+    pub fn flash(&self) -> Option<(u32, f32)> {
+        let (rows, start) = self.clear?;
+        let alpha = fade(start, ROW_FLASH_DURATION, get_time());
+        return (alpha > 0.0).then_some((rows, alpha));
+    }
+
+    /// How many rows above its resting place row `row` of the board is drawn, the stack
+    /// holding still while the cleared rows flash and then falling into the gap.
+    pub fn row_offset(&self, row: usize) -> f32 {
+        let Some((rows, start)) = self.clear else {
+            return 0.0;
+        };
+        let elapsed = get_time() - start - ROW_FLASH_DURATION;
+        let left = 1.0 - (elapsed / COLLAPSE_DURATION).clamp(0.0, 1.0) as f32;
+
+        // Rows kept keep their order, so the row ends up as many rows lower as there were
+        // cleared rows below it.
+        let mut kept = 0;
+        let cleared = rows.count_ones() as usize;
+        let mut original = None;
+        for y in 0..BOARD_HEIGHT {
+            if rows & row_bit(y as i32) == 0 {
+                if kept + cleared == row {
+                    original = Some(y);
+                }
+                kept += 1;
+            }
+        }
+        let drop = match original {
+            Some(original) => row - original,
+            // New empty rows come in from above the board.
+            None => cleared,
+        };
+        return drop as f32 * left;
+    }
+
+    /// Blocks of recently locked pieces to flash, and how bright.
+    pub fn lock_flashes(&self) -> impl Iterator<Item = (&[(i32, i32)], f32)> {
+        let now = get_time();
+        self.locks
+            .iter()
+            .map(move |(cells, start)| (cells.as_slice(), fade(*start, LOCK_FLASH_DURATION, now)))
+    }
+
+    /// Offset in blocks to draw the board at while it shakes.
+    pub fn shake(&self) -> Vec2 {
+        let Some(start) = self.shake else {
+            return Vec2::ZERO;
+        };
+        let t = get_time() - start;
+        let strength = SHAKE_STRENGTH * fade(start, SHAKE_DURATION, get_time());
+        return Vec2::new((t * 70.0).sin() as f32, (t * 55.0).cos() as f32) * strength;
+    }
+
+    /// Throws particles out of the T that just locked.
+    fn burst_piece(&mut self, now: f64) {
+        let Some((cells, _)) = self.locks.last() else {
+            return;
+        };
+        let origins: Vec<Vec2> = cells
+            .iter()
+            .map(|&(x, y)| Vec2::new(x as f32 + 0.5, y as f32 + 0.5))
+            .collect();
+        for origin in origins {
+            for _ in 0..PARTICLE_COUNT / 4 {
+                self.spawn_particle(origin, T_ID, now);
+            }
+        }
+    }
+
+    /// Throws particles of every color out of the rows of a perfect clear.
+    fn burst_rows(&mut self, rows: u32, now: f64) {
+        let cleared = (0..BOARD_HEIGHT).filter(|&y| rows & row_bit(y as i32) != 0);
+        let rows: Vec<usize> = cleared.collect();
+        for i in 0..PARTICLE_COUNT * 2 {
+            let row = rows[i % rows.len()];
+            let origin = Vec2::new(rand::gen_range(0.0, BOARD_WIDTH as f32), row as f32 + 0.5);
+            self.spawn_particle(origin, i % SHAPES.len(), now);
+        }
+    }
+
+    fn spawn_particle(&mut self, origin: Vec2, piece: usize, now: f64) {
+        let angle = rand::gen_range(0.0, std::f32::consts::TAU);
+        let speed = rand::gen_range(3.0, 9.0);
+        // Thrown upwards more than down, to arc before falling away.
+        let velocity = Vec2::from_angle(angle) * speed - Vec2::new(0.0, 4.0);
+        self.particles.push(Particle {
+            origin,
+            velocity,
+            piece,
+            start: now,
+        });
+    }
+}
+
+/// How many of the cleared `rows` were below row `row`, which is how far it falls.
+fn cleared_below(rows: u32, row: i32) -> i32 {
+    // Rows are counted from the top, so those below are the higher bits.
+    let below = rows.checked_shr((row + 1).max(0) as u32).unwrap_or(0);
+    return below.count_ones() as i32;
+}
+
+fn row_bit(row: i32) -> u32 {
+    if (0..BOARD_HEIGHT as i32).contains(&row) {
+        1 << row
+    } else {
+        0
+    }
+}
+
+/// Goes from 1 at `start` down to 0 once `duration` seconds have passed.
+fn fade(start: f64, duration: f64, now: f64) -> f32 {
+    return (1.0 - (now - start) / duration).clamp(0.0, 1.0) as f32;
+}
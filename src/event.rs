@@ -0,0 +1,127 @@
+use crate::global::SHAPES;
+use crate::tetromino::Tetromino;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+// Named after the guideline's top out conditions.
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TopOut {
+    /// A new piece spawned overlapping the stack.
+    BlockOut,
+    /// A piece locked with every block above the visible field.
+    LockOut,
+    /// Garbage rising from below pushed blocks off the top of the board.
+    PushOut,
+}
+
+/// Where a piece stood on the board, for effects to find its blocks.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Placement {
+    pub id: usize,
+    pub rotation: usize,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Placement {
+    pub fn of(piece: &Tetromino) -> Self {
+        Placement {
+            id: piece.id,
+            rotation: piece.rotation,
+            x: piece.pos.x as i32,
+            y: piece.pos.y as i32,
+        }
+    }
+
+    /// Board positions of the blocks of the piece, as `(x, y)`.
+    pub fn cells(self) -> impl Iterator<Item = (i32, i32)> {
+        let shape = SHAPES[self.id].orientations[self.rotation];
+        (0..4).flat_map(move |y| {
+            (0..4)
+                .filter(move |&x| shape[y][x])
+                .map(move |x| (self.x + x as i32, self.y + y as i32))
+        })
+    }
+}
+
+/// Something that happened during a tick, for the renderer and other observers to react to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Event {
+    /// The falling piece moved a column sideways.
+    Move,
+    /// The falling piece rotated, kicked or not.
+    Rotate,
+    /// The falling piece was swapped with the held one.
+    Hold,
+    /// A piece dropped straight down `rows` rows, about to lock.
+    HardDrop {
+        piece: Placement,
+        rows: u32,
+    },
+    /// A piece locked into the stack, before any lines it completed are cleared.
+    Lock(Placement),
+    Clear {
+        lines: u32,
+        /// Board rows that were cleared, one bit per row counted from the top.
+        rows: u32,
+        spin: Spin,
+        combo: i32,
+        back_to_back: bool,
+        perfect_clear: bool,
+    },
+    /// Garbage lines sent to the opponent, left over after cancelling incoming garbage.
+    Attack(u32),
+    LevelUp(u32),
+    /// Whole seconds left on the clock, sent once per second near the end of a timed game.
+    Countdown(u32),
+    Finished,
+    GameOver(TopOut),
+}
+
+impl Event {
+    /// Popup text such as "T-SPIN DOUBLE", if the event deserves one.
+    pub fn label(&self) -> Option<String> {
+        match *self {
+            Event::Clear {
+                lines,
+                spin,
+                back_to_back,
+                perfect_clear,
+                ..
+            } => {
+                if perfect_clear {
+                    return Some("ALL CLEAR".to_owned());
+                }
+
+                let count = ["", " SINGLE", " DOUBLE", " TRIPLE"];
+                let name = match spin {
+                    Spin::Full => format!("T-SPIN{}", count[lines as usize]),
+                    Spin::Mini => format!("MINI T-SPIN{}", count[lines as usize]),
+                    Spin::None if lines == 4 => "TETRIS".to_owned(),
+                    Spin::None => return None,
+                };
+
+                if back_to_back {
+                    return Some(format!("B2B {}", name));
+                }
+                Some(name)
+            }
+            Event::Move
+            | Event::Rotate
+            | Event::Hold
+            | Event::HardDrop { .. }
+            | Event::Lock(_)
+            | Event::Attack(_)
+            | Event::LevelUp(_)
+            | Event::Countdown(_)
+            | Event::Finished
+            | Event::GameOver(_) => None,
+        }
+    }
+}
@@ -1,45 +1,76 @@
+use crate::codec::{self, Reader, Writer};
+use crate::config::{GameConfig, LockMode};
+use crate::event::{Event, Placement, Spin, TopOut};
+use crate::garbage::GarbageGenerator;
 use crate::global::*;
+use crate::input::{Action, Input};
+use crate::randomizer::Randomizer;
+use crate::speed::*;
 use crate::tetromino::Tetromino;
 use macroquad::prelude::*;
-use macroquad::rand::ChooseRandom;
-use macroquad::rand::RandGenerator;
-use std::mem::swap;
+use std::mem::{self, swap};
 
-pub struct Game<'a> {
-    controls: &'a Controls,
-    bag: Vec<usize>,
+pub struct Game {
+    pub config: GameConfig,
+    seed: u64,
+    randomizer: Box<dyn Randomizer>,
+    /// Pieces drawn from the randomizer so far.
+    dealt: u64,
     used_hold: bool,
     pub empty_hold: bool,
 
     pub board: Vec<Vec<(u8, u8, u8)>>,
 
     pub piece: Tetromino,
-    pub preview: Tetromino,
+    queue: Vec<Tetromino>,
     pub phantom: Tetromino,
     pub hold: Tetromino,
 
     pub score: u32,
     pub lines: u32,
     pub level: u32,
+    pub combo: i32,
+    pub back_to_back: i32,
+    pub pieces: u32,
+    pub top_out: Option<TopOut>,
+    pub finished: bool,
+    pub splits: Vec<u64>,
+    garbage: GarbageGenerator,
+    garbage_added: u32,
+    pub garbage_cleared: u32,
+    incoming: Vec<u32>,
+    pub lines_sent: u32,
 
-    direction: Vec2,
+    pub time: u64,
+    last_kick: Option<usize>,
+    events: Vec<Event>,
 
-    x_move_delay: u64,
-    y_move_delay: u64,
-    gravity_delay: u64,
-    lock_delay: u64,
+    shift_direction: f32,
+    last_pressed_direction: f32,
+    /// Milliseconds the direction has been held, up to the DAS.
+    das_charge: u64,
+    /// Milliseconds towards the next auto repeat, up to the ARR. A full charge makes the
+    /// first repeat come as soon as DAS is charged.
+    arr_charge: u64,
 
-    last_x_move: u64,
-    last_y_move: u64,
-    last_gravity: u64,
-    last_lock: u64,
+    gravity_progress: u64,
+    level_lines: u32,
+    lock_timer: u64,
+    lock_resets: u32,
+    lowest_row: f32,
+    /// Milliseconds left before the next piece spawns, the locked piece staying in `piece`
+    /// until then.
+    entry_delay: u64,
 }
 
-impl<'a> Game<'a> {
-    pub fn new(controls: &'a Controls) -> Self {
+impl Game {
+    /// Creates a game whose piece sequence is fully determined by `config` and `seed`.
+    pub fn new(config: GameConfig, seed: u64) -> Self {
         let mut game = Game {
-            controls,
-            bag: Vec::new(),
+            config,
+            seed,
+            randomizer: config.randomizer.create(seed),
+            dealt: 0,
             used_hold: false,
             empty_hold: true,
 
@@ -47,124 +78,541 @@ impl<'a> Game<'a> {
 
             piece: Tetromino::new(0, Vec2::ZERO),
             phantom: Tetromino::new(0, Vec2::ZERO),
-            preview: Tetromino::new(0, Vec2::ZERO),
+            queue: Vec::new(),
             hold: Tetromino::new(0, Vec2::ZERO),
 
             score: 0,
             lines: 0,
             level: 0,
+            combo: -1,
+            back_to_back: -1,
+            pieces: 0,
+            top_out: None,
+            finished: false,
+            splits: Vec::new(),
+            // Rotated so the holes do not follow the same rolls as the pieces. Garbage
+            // received from an opponent moves its hole between attacks.
+            garbage: GarbageGenerator::new(
+                seed.rotate_left(32),
+                config.dig.map_or(100, |dig| dig.messiness),
+            ),
+            garbage_added: 0,
+            garbage_cleared: 0,
+            incoming: Vec::new(),
+            lines_sent: 0,
 
-            direction: Vec2::ZERO,
+            time: 0,
+            last_kick: None,
+            events: Vec::new(),
 
-            x_move_delay: X_MOVE_DELAY,
-            y_move_delay: Y_MOVE_DELAY,
-            gravity_delay: GRAVITY_DELAY,
-            lock_delay: LOCK_DELAY,
+            shift_direction: 0.0,
+            last_pressed_direction: 0.0,
+            das_charge: 0,
+            arr_charge: 0,
 
-            last_x_move: 0,
-            last_y_move: 0,
-            last_gravity: 0,
-            last_lock: 0,
+            gravity_progress: 0,
+            level_lines: 0,
+            lock_timer: 0,
+            lock_resets: 0,
+            lowest_row: TETROMINO_SPAWN_POS.y,
+            entry_delay: 0,
         };
 
-        game.refill_bag();
-
-        game.piece = Tetromino::new(game.bag.pop().unwrap(), TETROMINO_SPAWN_POS);
-        game.preview = Tetromino::new(game.bag.pop().unwrap(), Vec2::ZERO);
+        for _ in 0..config.preview_count {
+            let next = game.deal();
+            game.queue.push(next);
+        }
+        game.fill_garbage();
+        game.spawn_tetromino();
+        game.update_phantom();
 
         return game;
     }
 
-    pub fn update(&mut self) {
-        self.input();
-        self.update_phantom();
+    pub fn seed(&self) -> u64 {
+        self.seed
     }
 
-    fn refill_bag(&mut self) {
-        self.bag = (0..SHAPES.len()).collect();
-        let mut rng = RandGenerator::new();
-        rng.srand(get_millis());
-        self.bag.shuffle_with_state(&mut rng);
+    pub fn is_over(&self) -> bool {
+        self.top_out.is_some() || self.finished
     }
 
-    fn update_bag(&mut self) {
-        self.piece = self.preview.clone();
-        self.piece.pos = TETROMINO_SPAWN_POS;
+    /// Whether the game is waiting for the next piece to spawn, with no piece in play.
+    pub fn is_waiting(&self) -> bool {
+        self.entry_delay > 0
+    }
 
-        if self.bag.is_empty() {
-            self.refill_bag();
+    /// Whether the result belongs in the high score table: the game reached its goal, if it
+    /// has one, and was played by the standard rules of its mode.
+    pub fn is_ranked(&self) -> bool {
+        return self.config.is_standard()
+            && !self.config.mode.is_versus()
+            && (self.finished || !self.config.has_goal());
+    }
+
+    /// Pieces placed per second of play.
+    pub fn pps(&self) -> f32 {
+        if self.time == 0 {
+            return 0.0;
         }
+        return self.pieces as f32 / (self.time as f32 / 1000.0);
+    }
 
-        self.preview = Tetromino::new(self.bag.pop().unwrap(), Vec2::ZERO);
-        self.used_hold = false;
+    /// Queues garbage sent by an opponent, to rise after the next placement that clears
+    /// no lines unless it is cancelled first.
+    pub fn receive_garbage(&mut self, lines: u32) {
+        if lines > 0 {
+            self.incoming.push(lines);
+        }
     }
 
-    fn input(&mut self) {
-        let time = get_millis();
-        self.direction = Vec2::ZERO;
+    /// Garbage lines waiting to rise.
+    pub fn pending_garbage(&self) -> u32 {
+        self.incoming.iter().sum()
+    }
 
-        if time - self.last_x_move >= self.x_move_delay {
-            if is_key_down(self.controls.left) {
-                self.direction.x = -1.0;
-                self.last_x_move = time;
-            }
+    /// Fingerprint of the simulation state, for spotting two copies of a game drifting apart.
+    ///
+    /// The state is encoded and hashed with FNV-1a, so builds made with different compilers
+    /// agree on it.
+    pub fn state_hash(&self) -> u64 {
+        let mut out = Writer(Vec::new());
+        for cell in self.board.iter().flatten() {
+            out.u8(cell.0);
+            out.u8(cell.1);
+            out.u8(cell.2);
+        }
+        out.u8(self.piece.id as u8);
+        out.u8(self.piece.rotation as u8);
+        out.float(self.piece.pos.x);
+        out.float(self.piece.pos.y);
+        out.u8(self.hold.id as u8);
+        out.bool(self.empty_hold);
+        out.bool(self.used_hold);
+        for next in &self.queue {
+            out.u8(next.id as u8);
+        }
+        for value in [self.score, self.lines, self.level, self.pieces] {
+            out.varint(value as u64);
+        }
+        out.varint(self.garbage_added as u64);
+        out.varint(self.garbage_cleared as u64);
+        out.varint(self.time);
+        out.varint(self.entry_delay);
+        out.varint(self.incoming.len() as u64);
+        for &lines in &self.incoming {
+            out.varint(lines as u64);
+        }
+        return codec::fnv1a(&out.0);
+    }
 
-            if is_key_down(self.controls.right) {
-                self.direction.x = 1.0;
-                self.last_x_move = time;
-            }
+    /// Writes everything needed to continue the game later with `restore`.
+    ///
+    /// Only games without an opponent can be saved: the random generators are not stored
+    /// but rewound on restore, which relies on garbage rising only from the dig goal.
+    pub fn save(&self, out: &mut Writer) {
+        self.config.encode(out);
+        out.varint(self.seed);
+        out.varint(self.dealt);
+        out.bool(self.used_hold);
+        out.bool(self.empty_hold);
+
+        for cell in self.board.iter().flatten() {
+            out.u8(cell.0);
+            out.u8(cell.1);
+            out.u8(cell.2);
+        }
+        for piece in [self.piece, self.hold] {
+            out.u8(piece.id as u8);
+            out.u8(piece.rotation as u8);
+            out.float(piece.pos.x);
+            out.float(piece.pos.y);
+        }
+        for next in &self.queue {
+            out.u8(next.id as u8);
         }
 
-        if time - self.last_y_move >= self.y_move_delay {
-            if is_key_down(self.controls.soft_drop) {
-                self.direction.y = 1.0;
-                self.last_y_move = time;
-            }
+        out.varint(self.score as u64);
+        out.varint(self.lines as u64);
+        out.varint(self.level as u64);
+        out.signed(self.combo as i64);
+        out.signed(self.back_to_back as i64);
+        out.varint(self.pieces as u64);
+        out.varint(self.splits.len() as u64);
+        for &split in &self.splits {
+            out.varint(split);
+        }
+        out.varint(self.garbage_added as u64);
+        out.varint(self.garbage_cleared as u64);
+
+        out.varint(self.time);
+        out.option(self.last_kick.map(|kick| kick as u64));
+        out.float(self.shift_direction);
+        out.float(self.last_pressed_direction);
+        out.varint(self.das_charge);
+        out.varint(self.arr_charge);
+        out.varint(self.gravity_progress);
+        out.varint(self.level_lines as u64);
+        out.varint(self.lock_timer);
+        out.varint(self.lock_resets as u64);
+        out.float(self.lowest_row);
+        out.varint(self.entry_delay);
+    }
+
+    /// Reads a game written by `save`, returning `None` if the data does not make sense.
+    pub fn restore(reader: &mut Reader) -> Option<Self> {
+        let config = GameConfig::decode(reader)?;
+        if config.mode.is_versus() {
+            return None;
         }
+        let mut game = Game::new(config, reader.varint()?);
+        game.events.clear();
+        game.top_out = None;
+        game.finished = false;
+
+        let dealt = reader.varint()?;
+        game.used_hold = reader.bool()?;
+        game.empty_hold = reader.bool()?;
 
-        if (time - self.last_gravity >= self.gravity_delay) && !is_key_down(self.controls.soft_drop) {
-            self.direction.y = 1.0;
-            self.last_gravity = time;
+        for cell in game.board.iter_mut().flatten() {
+            *cell = (reader.u8()?, reader.u8()?, reader.u8()?);
+        }
+        for piece in [&mut game.piece, &mut game.hold] {
+            *piece = Tetromino::new(read_id(reader)?, Vec2::ZERO);
+            piece.rotation = reader.u8()? as usize % 4;
+            piece.pos = Vec2::new(reader.float()?, reader.float()?);
+        }
+        for next in game.queue.iter_mut() {
+            *next = Tetromino::new(read_id(reader)?, Vec2::ZERO);
         }
 
-        if is_key_pressed(self.controls.hard_drop) {
+        game.score = reader.varint()? as u32;
+        game.lines = reader.varint()? as u32;
+        game.level = (reader.varint()? as u32).min(MAX_LEVEL);
+        game.combo = reader.signed()? as i32;
+        game.back_to_back = reader.signed()? as i32;
+        game.pieces = reader.varint()? as u32;
+        let split_count = reader.varint()?;
+        if split_count > (game.lines / SPLIT_LINES) as u64 {
+            return None;
+        }
+        game.splits = (0..split_count)
+            .map(|_| reader.varint())
+            .collect::<Option<_>>()?;
+        game.garbage_added = reader.varint()? as u32;
+        game.garbage_cleared = reader.varint()? as u32;
+
+        game.time = reader.varint()?;
+        game.last_kick = reader.option()?.map(|kick| kick as usize);
+        game.shift_direction = reader.float()?;
+        game.last_pressed_direction = reader.float()?;
+        game.das_charge = reader.varint()?;
+        game.arr_charge = reader.varint()?;
+        game.gravity_progress = reader.varint()?;
+        game.level_lines = reader.varint()? as u32;
+        game.lock_timer = reader.varint()?;
+        game.lock_resets = reader.varint()? as u32;
+        game.lowest_row = reader.float()?;
+        game.entry_delay = reader.varint()?;
+
+        // Each placement deals at most one piece, plus one for the first hold.
+        let most_dealt = config.preview_count as u64 + game.pieces as u64 + 2;
+        let most_garbage = config.dig.map_or(0, |dig| dig.goal);
+        let delays = config.delays;
+        let most_delay = delays.entry + delays.line_clear;
+        let handling = config.handling;
+        let direction = |direction: f32| [-1.0, 0.0, 1.0].contains(&direction);
+        if dealt > most_dealt
+            || game.garbage_added > most_garbage
+            || game.entry_delay > most_delay
+            || !direction(game.shift_direction)
+            || !direction(game.last_pressed_direction)
+            || game.das_charge > handling.das
+            || game.arr_charge > handling.arr
+            || game.gravity_progress >= GRAVITY_1G * 1000
+            || game.lock_timer > game.lock_delay()
+            || game.lock_resets > LOCK_RESET_LIMIT
+            || game.last_kick.is_some_and(|kick| kick >= 5)
+            || !game.lowest_row.is_finite()
+            || game.is_over()
+        {
+            return None;
+        }
+
+        // Draw the pieces and garbage rows dealt so far again to leave the generators
+        // where they were.
+        game.randomizer = config.randomizer.create(game.seed);
+        for _ in 0..dealt {
+            game.randomizer.next();
+        }
+        game.dealt = dealt;
+        game.garbage = GarbageGenerator::new(
+            game.seed.rotate_left(32),
+            config.dig.map_or(100, |dig| dig.messiness),
+        );
+        for _ in 0..game.garbage_added {
+            game.garbage.shift_hole();
+        }
+
+        game.update_phantom();
+        return Some(game);
+    }
+
+    /// Events emitted during the most recent update.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Upcoming pieces, the next one to spawn first.
+    pub fn queue(&self) -> &[Tetromino] {
+        &self.queue
+    }
+
+    /// Advances the simulation by `delta` milliseconds using the actions in `input`.
+    pub fn update(&mut self, input: &Input, delta: u64) {
+        self.events.clear();
+        if self.is_over() {
+            return;
+        }
+
+        let previous_time = self.time;
+        self.time += delta;
+        if self.check_time_limit(previous_time) {
+            return;
+        }
+
+        if self.entry_delay > 0 {
+            self.wait(input, delta);
+            return;
+        }
+
+        self.input(input, delta);
+        self.update_phantom();
+    }
+
+    /// Counts down the entry delay, spawning the next piece once it is over. Held
+    /// directions charge DAS meanwhile, so the new piece can shift straight away.
+    fn wait(&mut self, input: &Input, delta: u64) {
+        let direction = self.held_direction(input);
+        let handling = self.config.handling;
+        if direction != self.shift_direction {
+            self.shift_direction = direction;
+            self.das_charge = 0;
+        }
+        if direction != 0.0 {
+            self.das_charge = (self.das_charge + delta).min(handling.das);
+            // Repeats count again from the full charge, the first coming right after spawning.
+            self.arr_charge = handling.arr;
+        }
+
+        self.entry_delay = self.entry_delay.saturating_sub(delta);
+        if self.entry_delay == 0 {
+            self.spawn_tetromino();
+            self.update_phantom();
+        }
+    }
+
+    fn deal(&mut self) -> Tetromino {
+        self.dealt += 1;
+        return Tetromino::new(self.randomizer.next(), Vec2::ZERO);
+    }
+
+    fn next_tetromino(&mut self) -> Tetromino {
+        let next = self.deal();
+        if self.queue.is_empty() {
+            return next;
+        }
+        self.queue.push(next);
+        return self.queue.remove(0);
+    }
+
+    fn spawn_tetromino(&mut self) {
+        self.piece = self.next_tetromino();
+        self.piece.pos = TETROMINO_SPAWN_POS;
+        self.used_hold = false;
+        self.last_kick = None;
+        self.reset_lock();
+        self.check_block_out();
+    }
+
+    fn input(&mut self, input: &Input, delta: u64) {
+        if input.is_pressed(Action::HardDrop) {
             self.drop_tetromino();
             return;
         }
 
-        if is_key_pressed(self.controls.hold) && !self.used_hold {
+        if input.is_pressed(Action::Hold) && !self.used_hold {
             self.hold_tetromino();
             return;
         }
 
         let mut clockwise = false;
-        if is_key_pressed(self.controls.rotate_clockwise) {
+        if input.is_pressed(Action::RotateClockwise) {
             clockwise = true;
             self.rotate_tetromino(clockwise);
-        } else if is_key_pressed(self.controls.rotate_counterclockwise) {
+        } else if input.is_pressed(Action::RotateCounterclockwise) {
             self.rotate_tetromino(clockwise);
         }
 
-        if !self.check_collision(self.piece, Vec2::new(self.direction.x, 0.0))
-            && self.direction.x != 0.0
-        {
-            self.piece.pos.x += self.direction.x;
+        self.auto_shift(input, delta);
+        self.apply_gravity(input, delta);
+        self.update_lock(delta);
+    }
+
+    /// Lets the piece fall by however many rows the gravity of this level, sped up by soft
+    /// drop, has accumulated.
+    fn apply_gravity(&mut self, input: &Input, delta: u64) {
+        let handling = self.config.handling;
+        let mut gravity = self.config.gravity.gravity(self.level);
+        if input.is_down(Action::SoftDrop) {
+            gravity = if handling.sdf >= INFINITE_SDF {
+                GRAVITY_20G
+            } else {
+                gravity * handling.sdf as u64
+            };
         }
 
-        if !self.check_collision(self.piece, Vec2::new(0.0, self.direction.y))
-            && self.direction.y != 0.0
-        {
-            self.piece.pos.y += self.direction.y;
-            self.last_lock = 0;
-        } else if self.direction.y > 0.0 {
-            if self.last_lock == 0 {
-                self.last_lock = time;
-            } else if time - self.last_lock >= self.lock_delay {
-                self.place_tetromino();
+        if self.is_grounded() {
+            self.gravity_progress = 0;
+            return;
+        }
+
+        let row = GRAVITY_1G * 1000;
+        self.gravity_progress += delta * gravity * 60;
+        let mut rows = self.gravity_progress / row;
+        self.gravity_progress %= row;
+        if gravity >= GRAVITY_20G {
+            rows = BOARD_HEIGHT as u64;
+        }
+
+        for _ in 0..rows.min(BOARD_HEIGHT as u64) {
+            if self.is_grounded() {
+                break;
+            }
+            self.piece.pos.y += 1.0;
+            self.last_kick = None;
+            self.on_fall();
+        }
+    }
+
+    fn is_grounded(&mut self) -> bool {
+        self.check_collision(self.piece, Vec2::new(0.0, 1.0))
+    }
+
+    fn lock_delay(&self) -> u64 {
+        self.config.lock_delays.at(self.level)
+    }
+
+    fn reset_lock(&mut self) {
+        self.lock_timer = 0;
+        self.lock_resets = 0;
+        self.lowest_row = self.piece.pos.y;
+    }
+
+    /// Called after the piece moved down a row.
+    fn on_fall(&mut self) {
+        self.lock_timer = 0;
+        if self.piece.pos.y > self.lowest_row {
+            self.lowest_row = self.piece.pos.y;
+            self.lock_resets = 0;
+        }
+    }
+
+    /// Called after the piece shifted or rotated, `grounded` telling whether it rested on
+    /// the stack beforehand.
+    fn on_move(&mut self, grounded: bool) {
+        match self.config.lock_mode {
+            LockMode::Extended => {
+                if grounded && self.lock_resets < LOCK_RESET_LIMIT {
+                    self.lock_timer = 0;
+                    self.lock_resets += 1;
+                }
             }
+            LockMode::Infinite => self.lock_timer = 0,
+            LockMode::Classic => {}
         }
     }
 
+    fn update_lock(&mut self, delta: u64) {
+        if !self.is_grounded() {
+            return;
+        }
+
+        self.lock_timer += delta;
+        let out_of_resets =
+            self.config.lock_mode == LockMode::Extended && self.lock_resets >= LOCK_RESET_LIMIT;
+        if self.lock_timer >= self.lock_delay() || out_of_resets {
+            self.place_tetromino();
+        }
+    }
+
+    /// Moves the piece sideways with delayed auto shift, the most recently pressed
+    /// direction winning while both are held. The charge carries over to new pieces.
+    fn auto_shift(&mut self, input: &Input, delta: u64) {
+        let direction = self.held_direction(input);
+        if direction != self.shift_direction {
+            self.shift_direction = direction;
+            self.das_charge = 0;
+            self.arr_charge = self.config.handling.arr;
+            if direction != 0.0 {
+                self.shift(direction);
+            }
+            return;
+        }
+        if direction == 0.0 {
+            return;
+        }
+
+        let handling = self.config.handling;
+        self.das_charge += delta;
+        if self.das_charge < handling.das {
+            return;
+        }
+        // Time held beyond the DAS goes towards repeats.
+        self.arr_charge += self.das_charge - handling.das;
+        self.das_charge = handling.das;
+
+        if handling.arr == 0 {
+            self.arr_charge = 0;
+            while self.shift(direction) {}
+            return;
+        }
+
+        while self.arr_charge >= handling.arr {
+            self.shift(direction);
+            self.arr_charge -= handling.arr;
+        }
+    }
+
+    /// The direction held, the most recently pressed one while both are.
+    fn held_direction(&mut self, input: &Input) -> f32 {
+        if input.is_pressed(Action::Left) {
+            self.last_pressed_direction = -1.0;
+        }
+        if input.is_pressed(Action::Right) {
+            self.last_pressed_direction = 1.0;
+        }
+
+        return match (input.is_down(Action::Left), input.is_down(Action::Right)) {
+            (true, true) => self.last_pressed_direction,
+            (true, false) => -1.0,
+            (false, true) => 1.0,
+            (false, false) => 0.0,
+        };
+    }
+
+    fn shift(&mut self, direction: f32) -> bool {
+        if self.check_collision(self.piece, Vec2::new(direction, 0.0)) {
+            return false;
+        }
+        let grounded = self.is_grounded();
+        self.piece.pos.x += direction;
+        self.last_kick = None;
+        self.on_move(grounded);
+        self.events.push(Event::Move);
+        return true;
+    }
+
     fn check_collision(&mut self, tetromino: Tetromino, offset: Vec2) -> bool {
         for y in 0..4 {
             for x in 0..4 {
@@ -177,7 +625,7 @@ impl<'a> Game<'a> {
                     {
                         return true;
                     }
-                    if index.y > 0.0
+                    if index.y >= 0.0
                         && self.board[index.y as usize][index.x as usize] != BOARD_COLOR
                     {
                         return true;
@@ -189,7 +637,7 @@ impl<'a> Game<'a> {
     }
 
     fn update_phantom(&mut self) {
-        self.phantom = self.piece.clone();
+        self.phantom = self.piece;
         for _ in 0..BOARD_HEIGHT + 1 {
             if !self.check_collision(self.phantom, Vec2::new(0.0, 1.0)) {
                 self.phantom.pos.y += 1.0;
@@ -199,7 +647,7 @@ impl<'a> Game<'a> {
 
     fn rotate_tetromino(&mut self, clockwise: bool) {
         let from = self.piece.rotation as i32;
-        let mut test_piece = self.piece.clone();
+        let mut test_piece = self.piece;
         test_piece.rotate(clockwise);
         let to = test_piece.rotation as i32;
 
@@ -216,12 +664,16 @@ impl<'a> Game<'a> {
         };
 
         if let Some(kicks) = kicks {
-            for &(kx, ky) in kicks.iter() {
-                let offset = Vec2::new(kx as f32, ky as f32);
-                if !self.check_collision(test_piece.clone(), offset) {
+            for (i, &(kx, ky)) in kicks.iter().enumerate() {
+                // Kick tables use SRS notation, where positive y points up.
+                let offset = Vec2::new(kx as f32, -ky as f32);
+                if !self.check_collision(test_piece, offset) {
+                    let grounded = self.is_grounded();
                     self.piece.rotate(clockwise);
                     self.piece.pos += offset;
-                    self.last_lock = get_millis();
+                    self.last_kick = Some(i);
+                    self.on_move(grounded);
+                    self.events.push(Event::Rotate);
                     return;
                 }
             }
@@ -229,51 +681,108 @@ impl<'a> Game<'a> {
     }
 
     fn hold_tetromino(&mut self) {
-        self.last_lock = 0;
-
         if self.empty_hold {
-            self.hold = self.piece.clone();
-            self.update_bag();
+            self.hold = self.piece;
+            self.spawn_tetromino();
             self.empty_hold = false;
         } else {
             swap(&mut self.piece, &mut self.hold);
             self.piece.pos = TETROMINO_SPAWN_POS;
+            self.last_kick = None;
+            self.reset_lock();
+            self.check_block_out();
         }
 
         self.used_hold = true;
+        self.events.push(Event::Hold);
     }
 
     fn drop_tetromino(&mut self) {
+        if self.phantom.pos != self.piece.pos {
+            self.last_kick = None;
+        }
+        self.events.push(Event::HardDrop {
+            piece: Placement::of(&self.phantom),
+            rows: (self.phantom.pos.y - self.piece.pos.y) as u32,
+        });
         self.piece = self.phantom;
         self.place_tetromino();
     }
 
+    fn is_occupied(&self, pos: Vec2) -> bool {
+        if pos.x < 0.0 || pos.x >= BOARD_WIDTH as f32 || pos.y >= BOARD_HEIGHT as f32 {
+            return true;
+        }
+        return pos.y >= 0.0 && self.board[pos.y as usize][pos.x as usize] != BOARD_COLOR;
+    }
+
+    /// Applies the 3-corner rule to a T piece whose last successful move was a rotation.
+    fn detect_spin(&self) -> Spin {
+        let Some(kick) = self.last_kick else {
+            return Spin::None;
+        };
+        if self.piece.id != T_ID {
+            return Spin::None;
+        }
+
+        // Corners of the 3x3 box around the T center, clockwise from the top left.
+        let corners = [(0.0, 1.0), (2.0, 1.0), (2.0, 3.0), (0.0, 3.0)]
+            .map(|(x, y)| self.is_occupied(self.piece.pos + Vec2::new(x, y)));
+        let front = [self.piece.rotation, (self.piece.rotation + 1) % 4];
+
+        if corners.iter().filter(|&&corner| corner).count() < 3 {
+            return Spin::None;
+        }
+        if front.iter().all(|&i| corners[i]) || kick == 4 {
+            return Spin::Full;
+        }
+        return Spin::Mini;
+    }
+
     fn place_tetromino(&mut self) {
-        for y in 0..4 {
-            for x in 0..4 {
-                if self.piece.shape()[y][x] {
-                    let pos = self.piece.pos + Vec2::new(x as f32, y as f32);
-
-                    if pos.y <= -1.0 {
-                        self.game_over();
-                        return;
-                    } else if pos.y >= 0.0 && pos.y < BOARD_HEIGHT as f32 {
-                        self.board[pos.y as usize][pos.x as usize] = self.piece.color;
-                    }
-                }
-            }
+        let spin = self.detect_spin();
+
+        // The board has no hidden rows, so blocks above the visible field are lost. Only a
+        // piece locking entirely above it ends the game.
+        let blocks: Vec<Vec2> = (0..16)
+            .filter(|i| self.piece.shape()[i / 4][i % 4])
+            .map(|i| self.piece.pos + Vec2::new((i % 4) as f32, (i / 4) as f32))
+            .filter(|pos| pos.y >= 0.0 && pos.y < BOARD_HEIGHT as f32)
+            .collect();
+        if blocks.is_empty() {
+            self.game_over(TopOut::LockOut);
+            return;
+        }
+        for pos in blocks {
+            self.board[pos.y as usize][pos.x as usize] = self.piece.color;
         }
+        // The locked piece may wait out the entry delay, no longer timing anything.
+        self.lock_timer = 0;
 
-        self.clear_lines();
-        self.update_bag();
+        self.events.push(Event::Lock(Placement::of(&self.piece)));
+        self.pieces += 1;
+        let cleared = self.clear_lines(spin);
+        if self.is_over() {
+            return;
+        }
+
+        let delays = self.config.delays;
+        self.entry_delay = delays.entry + if cleared > 0 { delays.line_clear } else { 0 };
+        if self.entry_delay == 0 {
+            self.spawn_tetromino();
+        }
     }
 
-    fn clear_lines(&mut self) {
+    /// Clears the full rows and scores them, returning how many there were.
+    fn clear_lines(&mut self, spin: Spin) -> usize {
         let mut cleared_lines = Vec::new();
 
         for y in 0..BOARD_HEIGHT {
             if self.board[y].iter().all(|&cell| cell != BOARD_COLOR) {
                 cleared_lines.push(y);
+                if self.board[y].contains(&GARBAGE_COLOR) {
+                    self.garbage_cleared += 1;
+                }
             }
         }
 
@@ -284,14 +793,635 @@ impl<'a> Game<'a> {
 
         let new_lines = cleared_lines.len();
         self.lines += new_lines as u32;
-        self.score += SCORE_PER_LINE[new_lines] * (self.level + 1);
-        if self.level != self.lines / 10 {
-            self.level = self.lines / 10;
-            self.gravity_delay = (1000 / (self.level + 1) + 200) as u64;
+        let mut points = match spin {
+            Spin::None => SCORE_PER_LINE[new_lines],
+            Spin::Mini => MINI_TSPIN_SCORE[new_lines],
+            Spin::Full => TSPIN_SCORE[new_lines],
+        };
+
+        // Tetrises and T-spins that clear lines keep the back-to-back chain going,
+        // any other clear breaks it and placements without a clear leave it alone.
+        let difficult = new_lines == 4 || (new_lines > 0 && spin != Spin::None);
+        if new_lines > 0 {
+            self.combo += 1;
+            self.back_to_back = if difficult { self.back_to_back + 1 } else { -1 };
+        } else {
+            self.combo = -1;
+        }
+
+        let back_to_back = difficult && self.back_to_back > 0;
+        if back_to_back {
+            points = points * 3 / 2;
+        }
+        if self.combo > 0 {
+            points += COMBO_SCORE * self.combo as u32;
+        }
+
+        let perfect_clear = new_lines > 0
+            && self
+                .board
+                .iter()
+                .all(|row| row.iter().all(|&cell| cell == BOARD_COLOR));
+        if perfect_clear {
+            points += if back_to_back && new_lines == 4 {
+                B2B_PERFECT_CLEAR_SCORE
+            } else {
+                PERFECT_CLEAR_SCORE[new_lines]
+            };
+        }
+
+        self.score += points * (self.level + 1);
+
+        if new_lines > 0 || spin != Spin::None {
+            self.events.push(Event::Clear {
+                lines: new_lines as u32,
+                rows: cleared_lines.iter().map(|&row| 1 << row).sum(),
+                spin,
+                combo: self.combo,
+                back_to_back,
+                perfect_clear,
+            });
+        }
+
+        if new_lines > 0 {
+            let attack = self.attack(new_lines, spin, back_to_back, perfect_clear);
+            self.send_attack(attack);
+        } else {
+            self.take_garbage();
+        }
+
+        self.advance_level(new_lines as u32);
+        self.check_goal();
+        self.fill_garbage();
+        return new_lines;
+    }
+
+    /// Garbage lines a clear is worth in versus, following the guideline attack table.
+    fn attack(&self, lines: usize, spin: Spin, back_to_back: bool, perfect_clear: bool) -> u32 {
+        let mut attack = match spin {
+            Spin::None => ATTACK_PER_LINE[lines],
+            Spin::Mini => MINI_TSPIN_ATTACK[lines],
+            Spin::Full => TSPIN_ATTACK[lines],
+        };
+        if back_to_back {
+            attack += B2B_ATTACK;
+        }
+        attack += COMBO_ATTACK[(self.combo.max(0) as usize).min(COMBO_ATTACK.len() - 1)];
+        if perfect_clear {
+            attack += PERFECT_CLEAR_ATTACK;
+        }
+        return attack;
+    }
+
+    /// Cancels incoming garbage with `attack`, oldest first, and sends what is left over.
+    fn send_attack(&mut self, mut attack: u32) {
+        while attack > 0 && !self.incoming.is_empty() {
+            let cancelled = attack.min(self.incoming[0]);
+            self.incoming[0] -= cancelled;
+            attack -= cancelled;
+            if self.incoming[0] == 0 {
+                self.incoming.remove(0);
+            }
+        }
+
+        if attack > 0 {
+            self.lines_sent += attack;
+            self.events.push(Event::Attack(attack));
+        }
+    }
+
+    /// Raises all incoming garbage, every attack with its own hole.
+    fn take_garbage(&mut self) {
+        for lines in mem::take(&mut self.incoming) {
+            self.garbage.shift_hole();
+            let rows = (0..lines)
+                .map(|_| self.garbage.row_with_current_hole())
+                .collect();
+            self.add_garbage(rows);
+            if self.is_over() {
+                return;
+            }
+        }
+    }
+
+    /// Tops the garbage back up to the dig height while rows remain to be dug out, and
+    /// finishes the game once all of them have been cleared.
+    fn fill_garbage(&mut self) {
+        let Some(dig) = self.config.dig else {
+            return;
+        };
+
+        if self.garbage_cleared >= dig.goal {
+            if !self.finished {
+                self.finished = true;
+                self.events.push(Event::Finished);
+            }
+            return;
+        }
+
+        let on_board = self
+            .board
+            .iter()
+            .filter(|row| row.contains(&GARBAGE_COLOR))
+            .count();
+        let missing = dig.height.saturating_sub(on_board);
+        let count = missing.min((dig.goal - self.garbage_added) as usize);
+
+        let rows = (0..count).map(|_| self.garbage.row()).collect();
+        self.garbage_added += count as u32;
+        self.add_garbage(rows);
+    }
+
+    /// Pushes `rows` up from the bottom of the board, topping out if that lifts blocks
+    /// off the top.
+    fn add_garbage(&mut self, rows: Vec<Vec<(u8, u8, u8)>>) {
+        let count = rows.len().min(BOARD_HEIGHT);
+        let pushed_out = self.board[..count]
+            .iter()
+            .any(|row| row.iter().any(|&cell| cell != BOARD_COLOR));
+
+        self.board.drain(..count);
+        self.board.extend(rows.into_iter().take(count));
+
+        if pushed_out {
+            self.game_over(TopOut::PushOut);
+        }
+    }
+
+    fn check_goal(&mut self) {
+        let Some(goal) = self.config.line_goal else {
+            return;
+        };
+
+        let splits = self.lines.min(goal) / SPLIT_LINES;
+        while (self.splits.len() as u32) < splits {
+            self.splits.push(self.time);
+        }
+
+        if self.lines >= goal {
+            self.finished = true;
+            self.events.push(Event::Finished);
+        }
+    }
+
+    fn advance_level(&mut self, lines: u32) {
+        let previous = self.level;
+        let rule = self.config.level_rule;
+
+        if rule == LevelRule::PerPiece {
+            if self.level % 100 != 99 && self.level < MAX_LEVEL - 1 {
+                self.level += 1;
+            }
+            self.level = (self.level + lines).min(MAX_LEVEL);
+        } else {
+            self.level_lines += lines;
+            while let Some(goal) = rule.goal(self.level) {
+                if self.level_lines < goal {
+                    break;
+                }
+                self.level_lines -= goal;
+                self.level += 1;
+            }
+        }
+
+        if self.level != previous {
+            self.events.push(Event::LevelUp(self.level));
+        }
+    }
+
+    /// Counts down the final seconds of a timed game and ends it once time runs out.
+    fn check_time_limit(&mut self, previous_time: u64) -> bool {
+        let Some(limit) = self.config.time_limit else {
+            return false;
+        };
+
+        let seconds_left = |time: u64| limit.saturating_sub(time).div_ceil(1000);
+        let seconds = seconds_left(self.time);
+        if seconds < seconds_left(previous_time) && (1..=COUNTDOWN_SECONDS).contains(&seconds) {
+            self.events.push(Event::Countdown(seconds as u32));
+        }
+
+        if self.time >= limit {
+            self.time = limit;
+            self.finished = true;
+            self.events.push(Event::Finished);
+            return true;
+        }
+        return false;
+    }
+
+    fn check_block_out(&mut self) {
+        if self.check_collision(self.piece, Vec2::ZERO) {
+            self.game_over(TopOut::BlockOut);
+        }
+    }
+
+    fn game_over(&mut self, top_out: TopOut) {
+        self.top_out = Some(top_out);
+        self.events.push(Event::GameOver(top_out));
+    }
+}
+
+fn read_id(reader: &mut Reader) -> Option<usize> {
+    let id = reader.u8()? as usize;
+    return (id < SHAPES.len()).then_some(id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Delays, Handling};
+    use crate::mode::Mode;
+
+    const TICK: u64 = 16;
+
+    fn input(down: &[Action], pressed: &[Action]) -> Input {
+        let mut input = Input::default();
+        for &action in down {
+            input.set_down(action);
+        }
+        for &action in pressed {
+            input.set_pressed(action);
+        }
+        return input;
+    }
+
+    /// Fills row `y` of the board but for the `holes`.
+    fn fill(game: &mut Game, y: usize, holes: &[usize]) {
+        for x in 0..BOARD_WIDTH {
+            if !holes.contains(&x) {
+                game.board[y][x] = GARBAGE_COLOR;
+            }
+        }
+    }
+
+    /// Locks a piece at `(x, y)` as though its last move was the rotation taking `kick`,
+    /// returning the clear it scored.
+    fn place(
+        game: &mut Game,
+        id: usize,
+        rotation: usize,
+        x: f32,
+        y: f32,
+        kick: Option<usize>,
+    ) -> Option<Event> {
+        game.piece = Tetromino::new(id, Vec2::new(x, y));
+        game.piece.rotation = rotation;
+        game.last_kick = kick;
+        game.events.clear();
+        game.place_tetromino();
+        return game
+            .events()
+            .iter()
+            .find(|event| matches!(event, Event::Clear { .. }))
+            .copied();
+    }
+
+    fn spin_of(clear: Option<Event>) -> Option<(u32, Spin)> {
+        match clear? {
+            Event::Clear { lines, spin, .. } => Some((lines, spin)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn held_direction_repeats_right_after_line_clear_delay() {
+        let config = GameConfig {
+            handling: Handling {
+                das: 100,
+                arr: 20,
+                sdf: 20,
+            },
+            delays: Delays::ANIMATED,
+            ..GameConfig::default()
+        };
+        let mut game = Game::new(config, 1);
+        game.piece = Tetromino::new(I_ID, TETROMINO_SPAWN_POS);
+        // The bottom row is full but for the four columns on the right, where the I fits.
+        for x in 0..BOARD_WIDTH - 4 {
+            game.board[BOARD_HEIGHT - 1][x] = GARBAGE_COLOR;
+        }
+
+        // Long enough for the piece to reach the wall and auto shift many times.
+        game.update(&input(&[Action::Right], &[Action::Right]), TICK);
+        for _ in 0..20 {
+            game.update(&input(&[Action::Right], &[]), TICK);
         }
+        game.update(&input(&[Action::Right], &[Action::HardDrop]), TICK);
+        assert!(game
+            .events()
+            .iter()
+            .any(|event| matches!(event, Event::Clear { lines: 1, .. })));
+        assert!(game.is_waiting());
+
+        while game.is_waiting() {
+            game.update(&input(&[Action::Right], &[]), TICK);
+        }
+        game.update(&input(&[Action::Right], &[]), TICK);
+        assert!(game.piece.pos.x > TETROMINO_SPAWN_POS.x);
+        assert!(game.events().contains(&Event::Move));
+    }
+
+    #[test]
+    fn state_hash_follows_the_state() {
+        let mut first = Game::new(GameConfig::default(), 3);
+        let mut second = Game::new(GameConfig::default(), 3);
+        assert_eq!(first.state_hash(), second.state_hash());
+
+        for game in [&mut first, &mut second] {
+            game.update(&input(&[Action::Left], &[Action::Left]), TICK);
+        }
+        assert_eq!(first.state_hash(), second.state_hash());
+
+        second.receive_garbage(1);
+        assert_ne!(first.state_hash(), second.state_hash());
+        first.receive_garbage(1);
+        assert_eq!(first.state_hash(), second.state_hash());
+
+        second.update(&input(&[], &[Action::HardDrop]), TICK);
+        first.update(&input(&[], &[]), TICK);
+        assert_ne!(first.state_hash(), second.state_hash());
+
+        // Parts of the state no piece on the board shows yet.
+        let game = Game::new(GameConfig::default(), 3);
+        let changes: [fn(&mut Game); 6] = [
+            |game| game.empty_hold = false,
+            |game| game.hold.id = (game.hold.id + 1) % SHAPES.len(),
+            |game| game.queue[1].id = (game.queue[1].id + 1) % SHAPES.len(),
+            |game| game.level += 1,
+            |game| game.garbage_added += 1,
+            |game| game.garbage_cleared += 1,
+        ];
+        for change in changes {
+            let mut changed = Game::new(GameConfig::default(), 3);
+            change(&mut changed);
+            assert_ne!(game.state_hash(), changed.state_hash());
+        }
+    }
+
+    #[test]
+    fn kick_tables_undo_each_other() {
+        for table in [JLSTZ_KICKS, I_KICKS] {
+            for ((from, to), kicks) in table {
+                let (_, back) = table
+                    .iter()
+                    .find(|((f, t), _)| *f == to && *t == from)
+                    .unwrap();
+                assert_eq!(kicks[0], (0, 0));
+                for (kick, undo) in kicks.iter().zip(back) {
+                    assert_eq!((kick.0, kick.1), (-undo.0, -undo.1), "{} to {}", from, to);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rotations_kick_off_walls_and_floor() {
+        let mut game = Game::new(GameConfig::default(), 1);
+        // Upright against the left wall, the flat side of the T does not fit until it kicks
+        // one column right.
+        game.piece = Tetromino::new(T_ID, Vec2::new(-1.0, 10.0));
+        game.piece.rotation = 1;
+        game.rotate_tetromino(false);
+        assert_eq!(game.piece.rotation, 0);
+        assert_eq!(game.piece.pos, Vec2::new(0.0, 10.0));
+        assert_eq!(game.last_kick, Some(1));
+
+        // Flat on the floor, it only stands up by kicking left and up a row.
+        game.piece = Tetromino::new(T_ID, Vec2::new(4.0, 17.0));
+        game.rotate_tetromino(true);
+        assert_eq!(game.piece.rotation, 1);
+        assert_eq!(game.piece.pos, Vec2::new(3.0, 16.0));
+        assert_eq!(game.last_kick, Some(2));
+
+        // Boxed in on every side, the rotation fails and the piece stays as it was.
+        game.piece = Tetromino::new(T_ID, Vec2::new(3.0, 17.0));
+        game.last_kick = None;
+        fill(&mut game, 17, &[4]);
+        fill(&mut game, 18, &[3, 4, 5]);
+        game.board[19][2] = GARBAGE_COLOR;
+        game.rotate_tetromino(true);
+        assert_eq!(game.piece.rotation, 0);
+        assert_eq!(game.last_kick, None);
     }
 
-    fn game_over(&mut self) {
-        *self = Game::new(&self.controls);
+    #[test]
+    fn t_spins_follow_the_three_corner_rule() {
+        // A T-spin double slot, the T pointing down into it under an overhang.
+        let mut game = Game::new(GameConfig::default(), 1);
+        game.board[17][3] = GARBAGE_COLOR;
+        fill(&mut game, 18, &[3, 4, 5]);
+        fill(&mut game, 19, &[4]);
+        let clear = place(&mut game, T_ID, 2, 3.0, 16.0, Some(0));
+        assert_eq!(spin_of(clear), Some((2, Spin::Full)));
+        assert_eq!(game.score, TSPIN_SCORE[2]);
+
+        // Pointing up with only one of its front corners filled, it is a mini unless the
+        // last kick was the far one.
+        for (kick, spin) in [
+            (Some(0), Some(Spin::Mini)),
+            (Some(4), Some(Spin::Full)),
+            (None, None),
+        ] {
+            let mut game = Game::new(GameConfig::default(), 1);
+            game.board[18][3] = GARBAGE_COLOR;
+            fill(&mut game, 19, &[3, 4, 5]);
+            let clear = place(&mut game, T_ID, 0, 3.0, 17.0, kick);
+            assert_eq!(
+                spin_of(clear),
+                Some((1, spin.unwrap_or(Spin::None))),
+                "{:?}",
+                kick
+            );
+        }
+
+        // Other pieces never spin, however boxed in.
+        let mut game = Game::new(GameConfig::default(), 1);
+        fill(&mut game, 17, &[9]);
+        fill(&mut game, 19, &[3, 4, 5, 6]);
+        let clear = place(&mut game, I_ID, 0, 3.0, 18.0, Some(0));
+        assert_eq!(spin_of(clear), Some((1, Spin::None)));
+    }
+
+    #[test]
+    fn scores_follow_back_to_back_and_combo() {
+        let mut game = Game::new(GameConfig::default(), 1);
+        let tetris = |game: &mut Game| {
+            for y in 16..20 {
+                fill(game, y, &[9]);
+            }
+            // Keeps the clear from being a perfect clear.
+            game.board[15][0] = GARBAGE_COLOR;
+            return place(game, I_ID, 1, 7.0, 16.0, None).unwrap();
+        };
+
+        let first = tetris(&mut game);
+        assert!(matches!(
+            first,
+            Event::Clear {
+                lines: 4,
+                combo: 0,
+                back_to_back: false,
+                ..
+            }
+        ));
+        assert_eq!(game.score, 800);
+
+        // Half as much again for back to back, and a combo bonus.
+        let second = tetris(&mut game);
+        assert!(matches!(
+            second,
+            Event::Clear {
+                lines: 4,
+                combo: 1,
+                back_to_back: true,
+                ..
+            }
+        ));
+        assert_eq!(game.score, 800 + 1200 + COMBO_SCORE);
+
+        // A single keeps the combo going but breaks the back to back chain.
+        fill(&mut game, 19, &[9]);
+        let single = place(&mut game, I_ID, 1, 7.0, 16.0, None).unwrap();
+        assert!(matches!(
+            single,
+            Event::Clear {
+                lines: 1,
+                combo: 2,
+                back_to_back: false,
+                ..
+            }
+        ));
+        assert_eq!(game.score, 800 + 1200 + COMBO_SCORE + 100 + 2 * COMBO_SCORE);
+        assert_eq!(game.back_to_back, -1);
+
+        // A placement without a clear ends the combo.
+        assert_eq!(place(&mut game, O_ID, 0, 0.0, 10.0, None), None);
+        assert_eq!(game.combo, -1);
+        assert_eq!(game.lines, 9);
+    }
+
+    #[test]
+    fn perfect_clears_score_a_bonus() {
+        let mut game = Game::new(GameConfig::default(), 1);
+        fill(&mut game, 19, &[6, 7, 8, 9]);
+        let clear = place(&mut game, I_ID, 0, 6.0, 18.0, None).unwrap();
+        assert!(matches!(
+            clear,
+            Event::Clear {
+                lines: 1,
+                perfect_clear: true,
+                ..
+            }
+        ));
+        assert_eq!(game.score, SCORE_PER_LINE[1] + PERFECT_CLEAR_SCORE[1]);
+    }
+
+    #[test]
+    fn attacks_add_up_the_guideline_table() {
+        let game = Game::new(GameConfig::default(), 1);
+        assert_eq!(game.attack(4, Spin::None, false, false), 4);
+        assert_eq!(game.attack(2, Spin::Full, true, false), 5);
+        assert_eq!(
+            game.attack(1, Spin::Mini, false, true),
+            PERFECT_CLEAR_ATTACK
+        );
+    }
+
+    #[test]
+    fn only_pieces_locking_wholly_above_the_field_lock_out() {
+        // An upright I with its top block above row 0 loses that block and play goes on.
+        let mut game = Game::new(GameConfig::default(), 1);
+        place(&mut game, I_ID, 1, 0.0, -1.0, None);
+        assert!(!game.is_over());
+        assert_eq!(game.pieces, 1);
+        assert!((0..3).all(|y| game.board[y][2] != BOARD_COLOR));
+
+        let mut game = Game::new(GameConfig::default(), 1);
+        place(&mut game, I_ID, 0, 3.0, -2.0, None);
+        assert_eq!(game.top_out, Some(TopOut::LockOut));
+        assert_eq!(game.pieces, 0);
+    }
+
+    fn restored(game: &Game) -> Option<Game> {
+        let mut out = Writer::new(b"TEST", 1);
+        game.save(&mut out);
+        return Game::restore(&mut Reader::new(&out.0, b"TEST", 1)?);
+    }
+
+    #[test]
+    fn saves_with_impossible_timers_are_rejected() {
+        let mut game = Game::new(GameConfig::default(), 5);
+        for _ in 0..30 {
+            game.update(&input(&[Action::Left], &[]), TICK);
+        }
+        assert!(restored(&game).is_some());
+
+        let corruptions: [fn(&mut Game); 10] = [
+            |game| game.das_charge = u64::MAX,
+            |game| game.arr_charge = game.config.handling.arr + 1,
+            |game| game.gravity_progress = GRAVITY_1G * 1000,
+            |game| game.lock_timer = game.lock_delay() + 1,
+            |game| game.lock_resets = LOCK_RESET_LIMIT + 1,
+            |game| game.shift_direction = 0.5,
+            |game| game.last_pressed_direction = -2.0,
+            |game| game.last_kick = Some(5),
+            |game| game.lowest_row = f32::NAN,
+            |game| game.lowest_row = f32::INFINITY,
+        ];
+        for (i, corrupt) in corruptions.iter().enumerate() {
+            let mut corrupted = restored(&game).unwrap();
+            corrupt(&mut corrupted);
+            assert!(restored(&corrupted).is_none(), "corruption {}", i);
+        }
+    }
+
+    #[test]
+    fn kicks_do_not_carry_over_to_the_next_piece() {
+        let mut game = Game::new(GameConfig::default(), 1);
+        game.last_kick = Some(4);
+        game.spawn_tetromino();
+        assert_eq!(game.last_kick, None);
+
+        // Both the first hold, which spawns a piece, and later swaps bring in an unkicked one.
+        for _ in 0..2 {
+            game.last_kick = Some(4);
+            game.hold_tetromino();
+            assert_eq!(game.last_kick, None);
+        }
+
+        // A T that spawns into a T-spin slot and locks without moving is no T-spin.
+        let mut game = Game::new(GameConfig::default(), 1);
+        game.board[0][3] = GARBAGE_COLOR;
+        fill(&mut game, 1, &[3, 4, 5]);
+        fill(&mut game, 2, &[9]);
+        game.last_kick = Some(4);
+        game.piece = Tetromino::new(T_ID, Vec2::ZERO);
+        game.queue[0] = Tetromino::new(T_ID, Vec2::ZERO);
+        game.spawn_tetromino();
+        game.events.clear();
+        game.place_tetromino();
+        assert_eq!(
+            game.events().iter().find_map(|event| match event {
+                Event::Clear { spin, .. } => Some(*spin),
+                _ => None,
+            }),
+            Some(Spin::None)
+        );
+    }
+
+    #[test]
+    fn lock_delay_shortens_as_marathon_levels_rise() {
+        let mut game = Game::new(Mode::Marathon.config(), 1);
+        let mut delays = Vec::new();
+        for level in [0, 9, 10, 15, 20, 500] {
+            game.level = level;
+            delays.push(game.lock_delay());
+        }
+        assert_eq!(delays, [500, 500, 450, 400, 350, 200]);
+
+        // Sprint stays at its first level, and so at the first delay.
+        let game = Game::new(Mode::Sprint.config(), 1);
+        assert_eq!(game.lock_delay(), 500);
     }
 }
@@ -0,0 +1,557 @@
+use crate::global::*;
+use std::fmt;
+use std::mem;
+
+/// Buttons of a gamepad, the face buttons named by their position so they mean the same
+/// on every layout.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Button {
+    DpadUp,
+    DpadDown,
+    DpadLeft,
+    DpadRight,
+    South,
+    East,
+    West,
+    North,
+    LeftShoulder,
+    RightShoulder,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+}
+
+pub const BUTTONS: [Button; 14] = [
+    Button::DpadUp,
+    Button::DpadDown,
+    Button::DpadLeft,
+    Button::DpadRight,
+    Button::South,
+    Button::East,
+    Button::West,
+    Button::North,
+    Button::LeftShoulder,
+    Button::RightShoulder,
+    Button::LeftTrigger,
+    Button::RightTrigger,
+    Button::Select,
+    Button::Start,
+];
+
+impl Button {
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+
+    /// Name of the button in the settings file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Button::DpadUp => "dpad_up",
+            Button::DpadDown => "dpad_down",
+            Button::DpadLeft => "dpad_left",
+            Button::DpadRight => "dpad_right",
+            Button::South => "south",
+            Button::East => "east",
+            Button::West => "west",
+            Button::North => "north",
+            Button::LeftShoulder => "left_shoulder",
+            Button::RightShoulder => "right_shoulder",
+            Button::LeftTrigger => "left_trigger",
+            Button::RightTrigger => "right_trigger",
+            Button::Select => "select",
+            Button::Start => "start",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        BUTTONS.into_iter().find(|button| button.name() == name)
+    }
+
+    /// Short name of the button as shown to the player, using the Xbox layout.
+    pub fn label(self) -> &'static str {
+        match self {
+            Button::DpadUp => "D-Pad ↑",
+            Button::DpadDown => "D-Pad ↓",
+            Button::DpadLeft => "D-Pad ←",
+            Button::DpadRight => "D-Pad →",
+            Button::South => "A",
+            Button::East => "B",
+            Button::West => "X",
+            Button::North => "Y",
+            Button::LeftShoulder => "LB",
+            Button::RightShoulder => "RB",
+            Button::LeftTrigger => "LT",
+            Button::RightTrigger => "RT",
+            Button::Select => "Select",
+            Button::Start => "Start",
+        }
+    }
+
+    /// The button reported by the evdev key code `code`.
+    fn from_key_code(code: u16) -> Option<Self> {
+        let button = match code {
+            BTN_SOUTH => Button::South,
+            BTN_EAST => Button::East,
+            BTN_NORTH => Button::North,
+            BTN_WEST => Button::West,
+            BTN_TL => Button::LeftShoulder,
+            BTN_TR => Button::RightShoulder,
+            BTN_TL2 => Button::LeftTrigger,
+            BTN_TR2 => Button::RightTrigger,
+            BTN_SELECT => Button::Select,
+            BTN_START => Button::Start,
+            BTN_DPAD_UP => Button::DpadUp,
+            BTN_DPAD_DOWN => Button::DpadDown,
+            BTN_DPAD_LEFT => Button::DpadLeft,
+            BTN_DPAD_RIGHT => Button::DpadRight,
+            _ => return None,
+        };
+        return Some(button);
+    }
+}
+
+// Event types and codes from `linux/input-event-codes.h`.
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const BTN_SOUTH: u16 = 0x130;
+const BTN_EAST: u16 = 0x131;
+const BTN_NORTH: u16 = 0x133;
+const BTN_WEST: u16 = 0x134;
+const BTN_TL: u16 = 0x136;
+const BTN_TR: u16 = 0x137;
+const BTN_TL2: u16 = 0x138;
+const BTN_TR2: u16 = 0x139;
+const BTN_SELECT: u16 = 0x13a;
+const BTN_START: u16 = 0x13b;
+const BTN_DPAD_UP: u16 = 0x220;
+const BTN_DPAD_DOWN: u16 = 0x221;
+const BTN_DPAD_LEFT: u16 = 0x222;
+const BTN_DPAD_RIGHT: u16 = 0x223;
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+
+/// Model of a controller, as USB vendor and product ids. Bindings are kept per model.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GamepadId {
+    pub vendor: u16,
+    pub product: u16,
+}
+
+impl fmt::Display for GamepadId {
+    /// Writes the id as `045e_028e`, the form used in the settings file.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04x}_{:04x}", self.vendor, self.product)
+    }
+}
+
+impl GamepadId {
+    pub fn parse(text: &str) -> Option<Self> {
+        let (vendor, product) = text.split_once('_')?;
+        return Some(GamepadId {
+            vendor: u16::from_str_radix(vendor, 16).ok()?,
+            product: u16::from_str_radix(product, 16).ok()?,
+        });
+    }
+}
+
+/// A controller found among the input devices.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct DeviceInfo {
+    /// Event device name such as `event20`, unique while it stays connected.
+    handler: String,
+    id: GamepadId,
+    name: String,
+}
+
+/// Picks the gamepads out of a listing in the format of `/proc/bus/input/devices`.
+///
+/// Devices are told apart from keyboards and mice by reporting the south face button.
+fn parse_devices(listing: &str) -> Vec<DeviceInfo> {
+    let mut devices = Vec::new();
+    for block in listing.split("\n\n") {
+        let mut handler = None;
+        let mut id = None;
+        let mut name = String::new();
+        let mut is_gamepad = false;
+
+        for line in block.lines() {
+            if let Some(ids) = line.strip_prefix("I: ") {
+                let field = |key: &str| {
+                    ids.split_whitespace()
+                        .find_map(|pair| pair.strip_prefix(key))
+                        .and_then(|value| u16::from_str_radix(value, 16).ok())
+                };
+                if let (Some(vendor), Some(product)) = (field("Vendor="), field("Product=")) {
+                    id = Some(GamepadId { vendor, product });
+                }
+            } else if let Some(value) = line.strip_prefix("N: Name=") {
+                name = value.trim_matches('"').to_owned();
+            } else if let Some(handlers) = line.strip_prefix("H: Handlers=") {
+                handler = handlers
+                    .split_whitespace()
+                    .find(|handler| handler.starts_with("event"))
+                    .map(str::to_owned);
+            } else if let Some(bits) = line.strip_prefix("B: KEY=") {
+                is_gamepad = has_bit(bits, BTN_SOUTH as usize);
+            }
+        }
+
+        if let (true, Some(handler), Some(id)) = (is_gamepad, handler, id) {
+            devices.push(DeviceInfo { handler, id, name });
+        }
+    }
+    return devices;
+}
+
+/// Whether `bit` is set in a bitmap written as hexadecimal words, most significant first.
+fn has_bit(bitmap: &str, bit: usize) -> bool {
+    let word_bits = usize::BITS as usize;
+    let words: Vec<&str> = bitmap.split_whitespace().rev().collect();
+    return words
+        .get(bit / word_bits)
+        .and_then(|word| u64::from_str_radix(word, 16).ok())
+        .is_some_and(|word| word >> (bit % word_bits) & 1 == 1);
+}
+
+/// A connected controller and the buttons held on it.
+pub struct Gamepad {
+    pub id: GamepadId,
+    pub name: String,
+    handler: String,
+    down: u32,
+    pressed: u32,
+    device: device::Device,
+}
+
+impl Gamepad {
+    pub fn is_down(&self, button: Button) -> bool {
+        self.down & button.bit() != 0
+    }
+
+    /// Whether the button went down since the previous poll.
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.pressed & button.bit() != 0
+    }
+
+    /// The first button pressed since the previous poll, in the order of `BUTTONS`.
+    pub fn last_pressed(&self) -> Option<Button> {
+        BUTTONS.into_iter().find(|&button| self.is_pressed(button))
+    }
+
+    /// Applies one input event, such as a button going down.
+    fn handle_event(&mut self, kind: u16, code: u16, value: i32) {
+        match (kind, code) {
+            (EV_KEY, _) => {
+                if let Some(button) = Button::from_key_code(code) {
+                    self.set(button, value != 0);
+                }
+            }
+            // Many controllers report the D-pad as a hat with -1, 0 or 1 on each axis.
+            (EV_ABS, ABS_HAT0X) => {
+                self.set(Button::DpadLeft, value < 0);
+                self.set(Button::DpadRight, value > 0);
+            }
+            (EV_ABS, ABS_HAT0Y) => {
+                self.set(Button::DpadUp, value < 0);
+                self.set(Button::DpadDown, value > 0);
+            }
+            _ => {}
+        }
+    }
+
+    fn set(&mut self, button: Button, down: bool) {
+        if down && !self.is_down(button) {
+            self.pressed |= button.bit();
+        }
+        if down {
+            self.down |= button.bit();
+        } else {
+            self.down &= !button.bit();
+        }
+    }
+}
+
+/// A controller plugged in or pulled out, with its name.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Connection {
+    Connected(String),
+    Disconnected(String),
+}
+
+/// Every connected controller, found again as they are plugged in and out.
+///
+/// Reads the Linux evdev devices directly, so a virtual controller made with uinput works
+/// like a real one. Other platforms see no controllers.
+pub struct Gamepads {
+    pads: Vec<Gamepad>,
+    last_scan: Option<u64>,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        Gamepads {
+            pads: Vec::new(),
+            last_scan: None,
+        }
+    }
+
+    /// Connected controllers, in the order they were found.
+    pub fn pads(&self) -> &[Gamepad] {
+        &self.pads
+    }
+
+    /// The controllers of `player` when `players` play at once: all of them alone, and
+    /// every other one in turn with two players.
+    pub fn of_player(&self, player: usize, players: usize) -> impl Iterator<Item = &Gamepad> {
+        self.pads
+            .iter()
+            .enumerate()
+            .filter(move |(i, _)| i % players == player)
+            .map(|(_, pad)| pad)
+    }
+
+    /// Whether `button` was pressed on any controller since the previous poll.
+    pub fn any_pressed(&self, button: Button) -> bool {
+        self.pads.iter().any(|pad| pad.is_pressed(button))
+    }
+
+    /// Reads what happened on every controller since the last call, and looks for
+    /// controllers plugged in or out every `GAMEPAD_SCAN_INTERVAL` milliseconds.
+    pub fn poll(&mut self) -> Vec<Connection> {
+        let mut changes = Vec::new();
+
+        let mut i = 0;
+        while i < self.pads.len() {
+            let pad = &mut self.pads[i];
+            pad.pressed = 0;
+            match pad.device.read() {
+                Some(events) => {
+                    for (kind, code, value) in events {
+                        pad.handle_event(kind, code, value);
+                    }
+                    i += 1;
+                }
+                None => changes.push(Connection::Disconnected(self.pads.remove(i).name)),
+            }
+        }
+
+        let now = get_millis();
+        if self
+            .last_scan
+            .is_some_and(|last| now - last < GAMEPAD_SCAN_INTERVAL)
+        {
+            return changes;
+        }
+        self.last_scan = Some(now);
+
+        let found = parse_devices(&device::listing());
+        let (kept, gone): (Vec<Gamepad>, Vec<Gamepad>) = mem::take(&mut self.pads)
+            .into_iter()
+            .partition(|pad| found.iter().any(|info| info.handler == pad.handler));
+        self.pads = kept;
+        changes.extend(
+            gone.into_iter()
+                .map(|pad| Connection::Disconnected(pad.name)),
+        );
+        for info in found {
+            if self.pads.iter().any(|pad| pad.handler == info.handler) {
+                continue;
+            }
+            if let Some(device) = device::Device::open(&info.handler) {
+                changes.push(Connection::Connected(info.name.clone()));
+                self.pads.push(Gamepad {
+                    id: info.id,
+                    name: info.name,
+                    handler: info.handler,
+                    down: 0,
+                    pressed: 0,
+                    device,
+                });
+            }
+        }
+        return changes;
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod device {
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{ErrorKind, Read};
+    use std::mem::size_of;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    /// An open evdev device, read without blocking.
+    pub struct Device {
+        file: File,
+        buffer: Vec<u8>,
+    }
+
+    /// The list of input devices the kernel knows about.
+    pub fn listing() -> String {
+        fs::read_to_string("/proc/bus/input/devices").unwrap_or_default()
+    }
+
+    impl Device {
+        /// Opens `/dev/input/<handler>`, which needs the player to be allowed to read it,
+        /// usually by being in the `input` group.
+        pub fn open(handler: &str) -> Option<Self> {
+            let file = OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(format!("/dev/input/{}", handler))
+                .ok()?;
+            return Some(Device {
+                file,
+                buffer: Vec::new(),
+            });
+        }
+
+        /// The events waiting as type, code and value, or `None` once the device is gone.
+        pub fn read(&mut self) -> Option<Vec<(u16, u16, i32)>> {
+            let mut chunk = [0; 1024];
+            loop {
+                match self.file.read(&mut chunk) {
+                    Ok(0) => return None,
+                    Ok(read) => self.buffer.extend_from_slice(&chunk[..read]),
+                    Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                    Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                    Err(_) => return None,
+                }
+            }
+
+            // Each event is a timestamp followed by a u16 type, a u16 code and an i32 value.
+            let size = size_of::<libc::input_event>();
+            let complete = self.buffer.len() / size * size;
+            let events = self.buffer[..complete]
+                .chunks_exact(size)
+                .map(|event| {
+                    let fields = &event[size - 8..];
+                    (
+                        u16::from_ne_bytes([fields[0], fields[1]]),
+                        u16::from_ne_bytes([fields[2], fields[3]]),
+                        i32::from_ne_bytes([fields[4], fields[5], fields[6], fields[7]]),
+                    )
+                })
+                .collect();
+            self.buffer.drain(..complete);
+            return Some(events);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod device {
+    pub struct Device;
+
+    pub fn listing() -> String {
+        String::new()
+    }
+
+    impl Device {
+        pub fn open(_handler: &str) -> Option<Self> {
+            None
+        }
+
+        pub fn read(&mut self) -> Option<Vec<(u16, u16, i32)>> {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYBOARD: &str = r#"I: Bus=0011 Vendor=0001 Product=0001 Version=ab41
+N: Name="AT Translated Set 2 keyboard"
+P: Phys=isa0060/serio0/input0
+S: Sysfs=/devices/platform/i8042/serio0/input/input0
+U: Uniq=
+H: Handlers=sysrq kbd event0 leds 
+B: PROP=0
+B: EV=120013
+B: KEY=402000000 3803078f800d001 feffffdfffefffff fffffffffffffffe
+B: MSC=10
+B: LED=7
+"#;
+
+    const MOUSE: &str = r#"I: Bus=0003 Vendor=046d Product=c077 Version=0111
+N: Name="Logitech USB Optical Mouse"
+P: Phys=usb-0000:00:14.0-1/input0
+S: Sysfs=/devices/pci0000:00/0000:00:14.0/usb1/1-1/1-1:1.0/0003:046D:C077.0001/input/input3
+U: Uniq=
+H: Handlers=mouse0 event3 
+B: PROP=0
+B: EV=17
+B: KEY=1f0000 0 0 0 0
+B: REL=903
+B: MSC=10
+"#;
+
+    const GAMEPAD: &str = r#"I: Bus=0003 Vendor=045e Product=028e Version=0114
+N: Name="Microsoft X-Box 360 pad"
+P: Phys=usb-0000:00:14.0-2/input0
+S: Sysfs=/devices/pci0000:00/0000:00:14.0/usb1/1-2/1-2:1.0/input/input20
+U: Uniq=
+H: Handlers=event20 js0 
+B: PROP=0
+B: EV=20000b
+B: KEY=7cdb000000000000 0 0 0 0
+B: ABS=3003f
+B: FF=107030000 0
+"#;
+
+    fn listing(blocks: &[&str]) -> String {
+        return blocks.join("\n");
+    }
+
+    #[test]
+    fn gamepads_are_found_among_other_devices() {
+        let devices = parse_devices(&listing(&[KEYBOARD, GAMEPAD, MOUSE]));
+        assert_eq!(
+            devices,
+            vec![DeviceInfo {
+                handler: "event20".to_owned(),
+                id: GamepadId {
+                    vendor: 0x045e,
+                    product: 0x028e,
+                },
+                name: "Microsoft X-Box 360 pad".to_owned(),
+            }]
+        );
+        assert_eq!(devices[0].id.to_string(), "045e_028e");
+    }
+
+    #[test]
+    fn keyboards_and_mice_are_not_gamepads() {
+        assert!(parse_devices(&listing(&[KEYBOARD, MOUSE])).is_empty());
+        assert!(parse_devices("").is_empty());
+    }
+
+    #[test]
+    fn malformed_devices_are_skipped() {
+        let malformed = [
+            GAMEPAD.replace("7cdb000000000000 0 0 0 0", "zz 0 0 0 0"),
+            GAMEPAD.replace("7cdb000000000000 0 0 0 0", "7cdb0000000000000000 0 0 0 0"),
+            GAMEPAD.replace("7cdb000000000000 0 0 0 0", ""),
+            GAMEPAD.replace("B: KEY=7cdb000000000000 0 0 0 0\n", ""),
+            GAMEPAD.replace("Vendor=045e ", ""),
+            GAMEPAD.replace("Product=028e", "Product=xyz"),
+            GAMEPAD.replace("event20 ", ""),
+        ];
+        for device in &malformed {
+            assert!(parse_devices(device).is_empty(), "{}", device);
+        }
+    }
+
+    #[test]
+    fn bitmaps_count_from_the_last_word() {
+        assert!(has_bit("1", 0));
+        assert!(!has_bit("1", 1));
+        assert!(has_bit("1 0", 64));
+        assert!(has_bit("8000000000000000", 63));
+        assert!(!has_bit("1", 64));
+        assert!(!has_bit("", 0));
+    }
+}
@@ -0,0 +1,45 @@
+use crate::global::*;
+use macroquad::rand::RandGenerator;
+
+/// Deals garbage rows that are full except for a single hole.
+pub struct GarbageGenerator {
+    rng: RandGenerator,
+    /// Chance in percent that the hole moves to another column between rows.
+    messiness: u32,
+    hole: usize,
+}
+
+impl GarbageGenerator {
+    pub fn new(seed: u64, messiness: u32) -> Self {
+        let rng = RandGenerator::new();
+        rng.srand(seed);
+        let hole = rng.gen_range(0, BOARD_WIDTH);
+
+        GarbageGenerator {
+            rng,
+            messiness,
+            hole,
+        }
+    }
+
+    /// Moves the hole to a different column with a chance of `messiness` percent.
+    pub fn shift_hole(&mut self) {
+        if self.rng.gen_range(0, 100) < self.messiness {
+            let offset = self.rng.gen_range(1, BOARD_WIDTH);
+            self.hole = (self.hole + offset) % BOARD_WIDTH;
+        }
+    }
+
+    /// The next garbage row, its hole possibly moved from the previous one.
+    pub fn row(&mut self) -> Vec<(u8, u8, u8)> {
+        self.shift_hole();
+        return self.row_with_current_hole();
+    }
+
+    /// A garbage row with its hole in the same column as the previous one.
+    pub fn row_with_current_hole(&self) -> Vec<(u8, u8, u8)> {
+        let mut row = vec![GARBAGE_COLOR; BOARD_WIDTH];
+        row[self.hole] = BOARD_COLOR;
+        return row;
+    }
+}
@@ -1,67 +1,171 @@
 use macroquad::prelude::*;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::OnceLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
+/// Milliseconds since the game started, on a clock that keeps going forward whatever
+/// happens to the system clock.
 pub fn get_millis() -> u64 {
+    static START: OnceLock<Instant> = OnceLock::new();
+    return START.get_or_init(Instant::now).elapsed().as_millis() as u64;
+}
+
+/// A seed for a new game, taken from the time of day.
+pub fn time_seed() -> u64 {
     return SystemTime::now()
         .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64;
+        .map_or(0, |since| since.as_millis() as u64);
 }
 
-pub fn get_color(color: (u8, u8, u8), a: u8) -> Color {
-    return Color::from_rgba(color.0, color.1, color.2, a);
+/// Formats a duration in milliseconds as `m:ss.mmm`.
+pub fn format_time(millis: u64) -> String {
+    return format!(
+        "{}:{:02}.{:03}",
+        millis / 60000,
+        millis / 1000 % 60,
+        millis % 1000
+    );
+}
+
+/// Formats how far `time` is ahead of (`-`) or behind (`+`) `reference` as `+s.mmm`.
+pub fn format_difference(time: u64, reference: u64) -> String {
+    let sign = if time <= reference { '-' } else { '+' };
+    let difference = time.abs_diff(reference);
+    return format!("{}{}.{:03}", sign, difference / 1000, difference % 1000);
+}
+
+/// Formats seconds since the Unix epoch as a `yyyy-mm-dd` UTC date.
+pub fn format_date(secs: u64) -> String {
+    // Days to civil date conversion from Howard Hinnant's `civil_from_days`.
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    return format!("{:04}-{:02}-{:02}", year, month, day);
 }
 
-pub fn get_pos(grid_pos: Vec2) -> Vec2 {
-    return grid_pos * BLOCK_SIZE;
+pub fn get_color(color: (u8, u8, u8), a: u8) -> Color {
+    return Color::from_rgba(color.0, color.1, color.2, a);
 }
 
-pub const FONT_COLOR: (u8, u8, u8) = (255, 255, 255);
+/// Colors marking empty and garbage cells of the board, drawn in the colors of the theme.
 pub const BOARD_COLOR: (u8, u8, u8) = (20, 20, 20);
-pub const UI_COLOR: (u8, u8, u8) = (30, 30, 30);
+pub const GARBAGE_COLOR: (u8, u8, u8) = (110, 110, 110);
+/// Opacity of a translucent ghost piece.
+pub const GHOST_ALPHA: u8 = 70;
 
 pub const FONT_SIZE: u16 = 32;
 pub const SMALL_FONT_SIZE: u16 = 26;
+pub const TITLE_FONT_SIZE: u16 = 72;
 
 pub const BOARD_WIDTH: usize = 10;
 pub const BOARD_HEIGHT: usize = 20;
 
+/// Size of a block in the window the game opens in, the layout scaling from there.
 pub const BLOCK_SIZE: f32 = 40.0;
+/// Size below which blocks stop shrinking with the window, parts of the game going off
+/// screen instead.
+pub const MIN_BLOCK_SIZE: f32 = 8.0;
 pub const GAME_SIDE_WIDTH: f32 = 5.5;
 pub const GAME_WIDTH: f32 = BOARD_WIDTH as f32 + GAME_SIDE_WIDTH * 2.0;
 
-pub const X_MOVE_DELAY: u64 = 130;
-pub const Y_MOVE_DELAY: u64 = 50;
-pub const GRAVITY_DELAY: u64 = 1000;
-pub const LOCK_DELAY: u64 = 170;
+pub const HIGH_SCORE_COUNT: usize = 10;
+pub const NAME_LENGTH: usize = 12;
+
+/// Most upcoming pieces the next queue can show.
+pub const MAX_PREVIEW: usize = 12;
+pub const PREVIEW_SPACING: f32 = 2.5;
+/// Rows of the right panel the next queue fills at most, six pieces at full size.
+pub const PREVIEW_HEIGHT: f32 = 6.0 * PREVIEW_SPACING;
+/// Row of the panel the first split is shown on, below the goal stats.
+pub const SPLITS_TOP: f32 = 16.5;
+pub const SPLIT_SPACING: f32 = 0.75;
+/// Splits that fit between the goal stats and the bottom of the panel, the latest shown.
+pub const SPLIT_ROWS: usize = ((BOARD_HEIGHT as f32 - SPLITS_TOP) / SPLIT_SPACING) as usize;
+
+pub const INFINITE_SDF: u32 = 41;
+/// Milliseconds between looks for controllers plugged in or out.
+pub const GAMEPAD_SCAN_INTERVAL: u64 = 1000;
+pub const DAS_STEP: u64 = 10;
+pub const MAX_DAS: u64 = 500;
+pub const ARR_STEP: u64 = 5;
+pub const MAX_ARR: u64 = 200;
+// Furthest the rules can be stretched, whether on the command line, in replays and saves or
+// over the network.
+pub const MAX_LINE_GOAL: u32 = 999;
+/// Longest time limit in milliseconds, an hour.
+pub const MAX_TIME_LIMIT: u64 = 3_600_000;
+/// Most garbage rows a dig keeps on the board, leaving room for pieces to spawn.
+pub const MAX_DIG_HEIGHT: usize = BOARD_HEIGHT - 4;
+/// Most lines a level can take under a fixed lines rule.
+pub const MAX_LEVEL_LINES: u32 = 100;
+/// Longest entry or line clear delay in milliseconds.
+pub const MAX_DELAY: u64 = 1000;
+/// Longest lock delay in milliseconds.
+pub const MAX_LOCK_DELAY: u64 = 5000;
+/// Most steps a lock delay table can have.
+pub const MAX_LOCK_STEPS: usize = 8;
+/// Volumes are percentages, changed in steps of this much.
+pub const VOLUME_STEP: u32 = 10;
+pub const MAX_LEVEL: u32 = 999;
+pub const SPLIT_LINES: u32 = 10;
+pub const COUNTDOWN_SECONDS: u64 = 10;
+pub const LOCK_RESET_LIMIT: u32 = 15;
 
-pub const TETROMINO_SPAWN_POS: Vec2 = Vec2::new(BOARD_WIDTH as f32 / 2.0 - 2.0, -3.0);
+pub const TETROMINO_SPAWN_POS: Vec2 = Vec2::new(BOARD_WIDTH as f32 / 2.0 - 2.0, -1.0);
 
 pub const SCORE_PER_LINE: [u32; 5] = [2, 100, 300, 500, 800];
+pub const TSPIN_SCORE: [u32; 4] = [400, 800, 1200, 1600];
+pub const MINI_TSPIN_SCORE: [u32; 4] = [100, 200, 400, 400];
+pub const COMBO_SCORE: u32 = 50;
+pub const PERFECT_CLEAR_SCORE: [u32; 5] = [0, 800, 1200, 1800, 2000];
+pub const B2B_PERFECT_CLEAR_SCORE: u32 = 3200;
 
-pub struct Controls {
-    pub left: KeyCode,
-    pub right: KeyCode,
-    pub soft_drop: KeyCode,
-    pub hard_drop: KeyCode,
-    pub rotate_clockwise: KeyCode,
-    pub rotate_counterclockwise: KeyCode,
-    pub hold: KeyCode,
-    pub pause: KeyCode,
-}
+pub const ATTACK_PER_LINE: [u32; 5] = [0, 0, 1, 2, 4];
+pub const TSPIN_ATTACK: [u32; 4] = [0, 2, 4, 6];
+pub const MINI_TSPIN_ATTACK: [u32; 4] = [0, 0, 1, 2];
+pub const B2B_ATTACK: u32 = 1;
+pub const COMBO_ATTACK: [u32; 11] = [0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+pub const PERFECT_CLEAR_ATTACK: u32 = 10;
+/// Most garbage one placement can send: a back to back T-spin triple clearing the board at
+/// the top of the combo table.
+pub const MAX_ATTACK: u32 =
+    TSPIN_ATTACK[3] + B2B_ATTACK + COMBO_ATTACK[COMBO_ATTACK.len() - 1] + PERFECT_CLEAR_ATTACK;
+
+pub const POPUP_DURATION: f64 = 1.5;
+
+// Effects, in seconds. The flash and collapse of cleared rows together fit the line clear
+// delay of `Delays::ANIMATED`.
+pub const ROW_FLASH_DURATION: f64 = 0.2;
+pub const COLLAPSE_DURATION: f64 = 0.1;
+pub const LOCK_FLASH_DURATION: f64 = 0.15;
+pub const TRAIL_DURATION: f64 = 0.2;
+pub const SHAKE_DURATION: f64 = 0.3;
+pub const PARTICLE_DURATION: f64 = 0.9;
+/// Furthest the board moves while shaking, in blocks.
+pub const SHAKE_STRENGTH: f32 = 0.25;
+pub const PARTICLE_COUNT: usize = 24;
+/// Downward acceleration of particles in blocks per second squared.
+pub const PARTICLE_GRAVITY: f32 = 30.0;
+
+pub const I_ID: usize = 0;
+pub const O_ID: usize = 3;
+pub const S_ID: usize = 4;
+pub const T_ID: usize = 5;
+pub const Z_ID: usize = 6;
 
-pub const CONTROLS_PLAYER1: Controls = Controls {
-    left: KeyCode::Left,
-    right: KeyCode::Right,
-    soft_drop: KeyCode::Down,
-    hard_drop: KeyCode::Space,
-    rotate_clockwise: KeyCode::Up,
-    rotate_counterclockwise: KeyCode::LeftControl,
-    hold: KeyCode::C,
-    pause: KeyCode::Escape,
-};
+pub type KickTable = [((i32, i32), [(i32, i32); 5]); 8];
 
-pub const JLSTZ_KICKS: [((i32, i32), [(i32, i32); 5]); 8] = [
+pub const JLSTZ_KICKS: KickTable = [
     ((0, 1), [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]),
     ((1, 0), [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]),
     ((1, 2), [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]),
@@ -72,7 +176,7 @@ pub const JLSTZ_KICKS: [((i32, i32), [(i32, i32); 5]); 8] = [
     ((0, 3), [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]),
 ];
 
-pub const I_KICKS: [((i32, i32), [(i32, i32); 5]); 8] = [
+pub const I_KICKS: KickTable = [
     ((0, 1), [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]),
     ((1, 0), [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)]),
     ((1, 2), [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)]),
@@ -0,0 +1,163 @@
+use crate::game::Game;
+use crate::global::*;
+use crate::mode::Mode;
+use crate::storage;
+use std::cmp::Ordering;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HEADER: &str = "tetris-highscores";
+const VERSION: u32 = 1;
+const FILE_NAME: &str = "highscores.txt";
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub mode: Mode,
+    pub score: u32,
+    pub lines: u32,
+    pub level: u32,
+    pub time: u64,
+    pub date: u64,
+    pub name: String,
+}
+
+impl Entry {
+    pub fn from_game(game: &Game, name: &str) -> Self {
+        Entry {
+            mode: game.config.mode,
+            score: game.score,
+            lines: game.lines,
+            level: game.level,
+            time: game.time,
+            date: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+            name: name.to_owned(),
+        }
+    }
+
+    fn ranks_above(&self, other: &Entry) -> bool {
+        if self.mode.ranks_by_time() {
+            return self.time < other.time;
+        }
+        return self.score > other.score;
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(7, '\t');
+        Some(Entry {
+            mode: Mode::from_name(fields.next()?)?,
+            score: fields.next()?.parse().ok()?,
+            lines: fields.next()?.parse().ok()?,
+            level: fields.next()?.parse().ok()?,
+            time: fields.next()?.parse().ok()?,
+            date: fields.next()?.parse().ok()?,
+            name: fields.next()?.to_owned(),
+        })
+    }
+
+    fn format(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.mode.name(),
+            self.score,
+            self.lines,
+            self.level,
+            self.time,
+            self.date,
+            self.name
+        )
+    }
+}
+
+/// The best results of every mode, kept in the data directory.
+pub struct HighScores {
+    path: Option<PathBuf>,
+    entries: Vec<Entry>,
+}
+
+impl HighScores {
+    /// Loads the table, starting empty if the file is missing, corrupted or from another version.
+    pub fn load() -> Self {
+        let path = storage::data_dir().map(|dir| dir.join(FILE_NAME));
+        let mut scores = HighScores {
+            path,
+            entries: Vec::new(),
+        };
+
+        let Some(path) = &scores.path else {
+            return scores;
+        };
+        let Ok(contents) = fs::read_to_string(path) else {
+            return scores;
+        };
+
+        let mut lines = contents.lines();
+        if lines.next() != Some(&format!("{} {}", HEADER, VERSION)) {
+            storage::back_up(path);
+            return scores;
+        }
+
+        scores.entries = lines.filter_map(Entry::parse).collect();
+        return scores;
+    }
+
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let mut contents = format!("{} {}\n", HEADER, VERSION);
+        for entry in &self.entries {
+            contents += &entry.format();
+            contents.push('\n');
+        }
+        let _ = fs::write(path, contents);
+    }
+
+    /// Best entries of `mode`, best first.
+    pub fn top(&self, mode: Mode) -> Vec<&Entry> {
+        let mut top: Vec<&Entry> = self.entries.iter().filter(|e| e.mode == mode).collect();
+        top.sort_by(|a, b| {
+            if a.ranks_above(b) {
+                Ordering::Less
+            } else if b.ranks_above(a) {
+                Ordering::Greater
+            } else {
+                a.date.cmp(&b.date)
+            }
+        });
+        top.truncate(HIGH_SCORE_COUNT);
+        return top;
+    }
+
+    /// Position `entry` would take in its mode's table, ties going to the older result.
+    fn rank(&self, entry: &Entry) -> usize {
+        let top = self.top(entry.mode);
+        top.iter()
+            .position(|other| entry.ranks_above(other))
+            .unwrap_or(top.len())
+    }
+
+    pub fn qualifies(&self, entry: &Entry) -> bool {
+        self.rank(entry) < HIGH_SCORE_COUNT
+    }
+
+    /// Records `entry` and returns its rank in its mode's table, if it made the cut.
+    pub fn insert(&mut self, entry: Entry) -> Option<usize> {
+        let rank = self.rank(&entry);
+        if rank >= HIGH_SCORE_COUNT {
+            return None;
+        }
+
+        let mode = entry.mode;
+        let mut top: Vec<Entry> = self.top(mode).into_iter().cloned().collect();
+        top.insert(rank, entry);
+        top.truncate(HIGH_SCORE_COUNT);
+
+        self.entries.retain(|e| e.mode != mode);
+        self.entries.extend(top);
+        return Some(rank);
+    }
+}
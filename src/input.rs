@@ -0,0 +1,58 @@
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Left,
+    Right,
+    SoftDrop,
+    HardDrop,
+    RotateClockwise,
+    RotateCounterclockwise,
+    Hold,
+}
+
+pub const ACTIONS: [Action; 7] = [
+    Action::Left,
+    Action::Right,
+    Action::SoftDrop,
+    Action::HardDrop,
+    Action::RotateClockwise,
+    Action::RotateCounterclockwise,
+    Action::Hold,
+];
+
+impl Action {
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// The actions held and newly pressed during a single tick.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Input {
+    pub down: u8,
+    pub pressed: u8,
+}
+
+impl Input {
+    pub fn is_down(&self, action: Action) -> bool {
+        self.down & action.bit() != 0
+    }
+
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.pressed & action.bit() != 0
+    }
+
+    pub fn set_down(&mut self, action: Action) {
+        self.down |= action.bit();
+    }
+
+    pub fn set_pressed(&mut self, action: Action) {
+        self.pressed |= action.bit();
+    }
+}
+
+/// One tick of a player: the milliseconds it lasted and the actions held during it.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Frame {
+    pub delta: u64,
+    pub input: Input,
+}
@@ -0,0 +1,52 @@
+use crate::global::*;
+use macroquad::prelude::*;
+
+/// Where the game is drawn in the window: a canvas of one or two boards scaled to fit the
+/// screen and centered, the space left over on either side being letterboxed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Layout {
+    /// Size of a block in points.
+    pub block: f32,
+    /// Top left corner of the canvas.
+    pub offset: Vec2,
+    pub size: Vec2,
+}
+
+impl Layout {
+    /// Fits a canvas of `boards` boards side by side into a screen of `screen` points, with
+    /// `dpi` pixels per point so blocks start and end on whole pixels.
+    pub fn fit(boards: usize, screen: Vec2, dpi: f32) -> Self {
+        let grid = Vec2::new(GAME_WIDTH * boards as f32, BOARD_HEIGHT as f32);
+        let fit = (screen / grid).min_element().max(MIN_BLOCK_SIZE);
+        let block = (fit * dpi).floor() / dpi;
+
+        let size = grid * block;
+        let offset = ((screen - size) * 0.5 * dpi).round() / dpi;
+        return Layout {
+            block,
+            offset: offset.max(Vec2::ZERO),
+            size,
+        };
+    }
+
+    /// The layout of the window this frame.
+    pub fn current(boards: usize) -> Self {
+        let screen = Vec2::new(screen_width(), screen_height());
+        return Self::fit(boards, screen, screen_dpi_scale());
+    }
+
+    /// How much larger than at the default window size everything is drawn.
+    pub fn scale(&self) -> f32 {
+        return self.block / BLOCK_SIZE;
+    }
+
+    /// Position on screen of a point on the grid of blocks.
+    pub fn grid_pos(&self, grid_pos: Vec2) -> Vec2 {
+        return self.offset + grid_pos * self.block;
+    }
+
+    /// Position on screen of a point given as a fraction of the canvas width and height.
+    pub fn relative(&self, x: f32, y: f32) -> Vec2 {
+        return self.offset + self.size * Vec2::new(x, y);
+    }
+}
@@ -1,44 +1,265 @@
 #![windows_subsystem = "windows"]
+#![allow(clippy::needless_return)]
 
+mod args;
 mod assets;
+mod audio;
+mod codec;
+mod config;
+mod controls;
+mod effects;
+mod event;
 mod game;
+mod gamepad;
+mod garbage;
 mod global;
+mod highscores;
+mod input;
+mod layout;
+mod menu;
+mod mode;
+mod net;
+mod randomizer;
+mod records;
 mod renderer;
+mod replay;
+mod save;
+mod screen;
+mod settings;
+mod speed;
+mod storage;
 mod tetromino;
+mod theme;
 
+use crate::args::Args;
 use crate::assets::Assets;
-use crate::game::Game;
+use crate::audio::Audio;
+use crate::gamepad::{Connection, Gamepads};
 use crate::global::*;
+use crate::highscores::HighScores;
+use crate::layout::Layout;
+use crate::menu::*;
+use crate::net::{Hello, Network, Session};
+use crate::records::Records;
 use crate::renderer::Renderer;
+use crate::replay::{Playback, Replay};
+use crate::save::Saved;
+use crate::screen::{new_match, Context, Screen, Screens};
+use crate::settings::Settings;
+use crate::theme::Themes;
 use macroquad::prelude::*;
 
 fn conf() -> Conf {
     Conf {
         window_title: "Tetris".to_owned(),
-        window_resizable: false,
+        window_resizable: true,
+        high_dpi: true,
         window_width: GAME_WIDTH as i32 * BLOCK_SIZE as i32,
         window_height: BOARD_HEIGHT as i32 * BLOCK_SIZE as i32,
         ..Default::default()
     }
 }
 
+/// Sizes the window for `boards` boards side by side with blocks of `block` points.
+fn fit_window(boards: usize, block: f32) {
+    request_new_screen_size(
+        GAME_WIDTH * boards as f32 * block,
+        BOARD_HEIGHT as f32 * block,
+    );
+}
+
 #[macroquad::main(conf)]
 async fn main() {
+    let args = Args::parse();
     let assets = Assets::new().await;
+    let audio = Audio::new(&assets);
+
+    let settings = Settings::load();
+    let themes = Themes::load();
+    let mut renderer = Renderer::new(&assets, themes.get(&settings.theme));
+    let mut config = args.config;
+    config.handling = settings.handling;
+    config.delays = settings.delays();
+    let seed = args.seed.unwrap_or_else(time_seed);
+    let (mut player, rival, mut recording) = new_match(config, seed);
+    let mut rival_renderer = Renderer::new(&assets, themes.get(&settings.theme));
+    rival_renderer.origin = Vec2::new(GAME_WIDTH, 0.0);
+
+    let mut screens = Screens::new(if args.replay.is_some() {
+        vec![Screen::Replay]
+    } else if args.network.is_some() {
+        vec![Screen::Connecting]
+    } else if args.play {
+        vec![Screen::Playing]
+    } else {
+        vec![Screen::Title(0)]
+    });
+
+    // A solo game left unfinished last time is picked up where it was, paused.
+    if args.network.is_none() && args.replay.is_none() && rival.is_none() {
+        match save::load() {
+            Saved::Game(game, replay) => {
+                player = *game;
+                recording = *replay;
+                screens = Screens::new(vec![Screen::Title(0), Screen::Playing, Screen::Paused(0)]);
+                renderer.show_popup("GAME RESUMED");
+            }
+            Saved::Rejected => renderer.show_popup("SAVE DISCARDED"),
+            Saved::None => {}
+        }
+    }
+
+    let records = Records::load();
+    renderer.personal_best = records.get(&player).cloned();
+    // Over the network the match starts once the other side has said hello.
+    let hello = Hello {
+        seed,
+        handling: settings.handling,
+    };
+    let connection = args.network.as_ref().map(|network| match network {
+        Network::Host(port) => Session::host(*port, hello),
+        Network::Connect(address) => Session::connect(address, hello),
+    });
+    let connect_error = match &connection {
+        Some(Err(error)) => Some(error.to_string()),
+        _ => None,
+    };
+    let session = connection.and_then(Result::ok);
 
-    let mut renderer = Renderer::new(&assets, &CONTROLS_PLAYER1);
-    let mut player = Game::new(&CONTROLS_PLAYER1);
+    let playback = args
+        .replay
+        .as_deref()
+        .and_then(Replay::load)
+        .map(Playback::new);
+
+    let mut ctx = Context {
+        settings,
+        themes: &themes,
+        renderer,
+        rival_renderer,
+        audio,
+        gamepads: Gamepads::new(),
+        high_scores: HighScores::load(),
+        records,
+        config,
+        player,
+        rival,
+        recording,
+        network: args.network,
+        session,
+        connect_error,
+        replay: args.replay,
+        playback,
+        last_name: String::new(),
+        navigation: None,
+        delta: 0,
+        pad_lost: false,
+    };
+    let mut boards = 1;
+    let mut fullscreen = false;
+    let mut last_frame = get_millis();
+    prevent_quit();
 
     loop {
-        clear_background(get_color(UI_COLOR, 255));
+        let settings = &mut ctx.settings;
+        if is_key_pressed(KeyCode::F11) {
+            settings.fullscreen = !settings.fullscreen;
+            settings.save();
+        }
+        // Typed names and keys being bound may well contain an M.
+        let typing = matches!(
+            screens.top(),
+            Screen::NameEntry(_) | Screen::Bindings { .. }
+        );
+        if is_key_pressed(KeyCode::M) && !typing {
+            settings.muted = !settings.muted;
+            settings.save();
+            let label = if settings.muted {
+                "SOUND OFF"
+            } else {
+                "SOUND ON"
+            };
+            ctx.renderer.show_popup(label);
+        }
+        ctx.audio
+            .set_volume(settings.sfx_volume, settings.music_volume, settings.muted);
+        if settings.fullscreen != fullscreen {
+            fullscreen = settings.fullscreen;
+            set_fullscreen(fullscreen);
+            if !fullscreen {
+                fit_window(boards, BLOCK_SIZE);
+            }
+        }
+
+        // A window sized by hand keeps its scale as a second board comes and goes.
+        let shown_boards = if ctx.rival.is_some() && screens.in_match() {
+            2
+        } else {
+            1
+        };
+        if shown_boards != boards {
+            if !fullscreen {
+                fit_window(shown_boards, Layout::current(boards).block);
+            }
+            boards = shown_boards;
+        }
+        let layout = Layout::current(boards);
+        ctx.renderer.layout = layout;
+        ctx.rival_renderer.layout = layout;
+        ctx.renderer.theme = themes.get(&settings.theme);
+        ctx.rival_renderer.theme = ctx.renderer.theme;
+
+        clear_background(BLACK);
+        ctx.renderer.draw_background();
 
-        renderer.update();
+        if is_quit_requested() {
+            let solo = ctx.session.is_none() && ctx.rival.is_none();
+            if screens.is_playing() && solo && !ctx.player.is_over() {
+                save::save(&ctx.player, &ctx.recording);
+            }
+            break;
+        }
+
+        ctx.pad_lost = false;
+        for connection in ctx.gamepads.poll() {
+            match connection {
+                Connection::Connected(_) => ctx.renderer.show_popup("CONTROLLER CONNECTED"),
+                Connection::Disconnected(_) => {
+                    ctx.renderer.show_popup("CONTROLLER DISCONNECTED");
+                    ctx.pad_lost = true;
+                }
+            }
+        }
 
-        if !renderer.paused {
-            player.update();
+        let now = get_millis();
+        ctx.navigation = Navigation::read(&ctx.gamepads);
+        ctx.delta = now - last_frame;
+        let transitions = screens.top_mut().update(&mut ctx);
+        if !screens.apply(transitions) {
+            break;
         }
+        last_frame = now;
 
-        renderer.draw(&player);
+        // The music plays along with a game or replay, and stops with it for menus.
+        let replaying = matches!(
+            (screens.top(), &ctx.playback),
+            (Screen::Replay, Some(playback)) if !playback.paused
+        );
+        ctx.audio
+            .set_music(matches!(screens.top(), Screen::Playing) || replaying);
+
+        if screens.in_match() {
+            // The second board goes first so menus over the match cover both.
+            if let Some(rival) = &ctx.rival {
+                ctx.rival_renderer.draw(rival, &ctx.settings.controls[1]);
+            }
+            let shown = match (screens.top(), &ctx.playback) {
+                (Screen::Replay, Some(playback)) => &playback.game,
+                _ => &ctx.player,
+            };
+            ctx.renderer.draw(shown, &ctx.settings.controls[0]);
+        }
+        screens.top().draw(&ctx);
 
         next_frame().await;
     }
@@ -0,0 +1,209 @@
+use crate::gamepad::{Button, Gamepads};
+use crate::global::*;
+use crate::settings::Settings;
+use crate::theme::Theme;
+use macroquad::prelude::*;
+
+/// A step taken through a menu.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Navigation {
+    Up,
+    Down,
+    Left,
+    Right,
+    Confirm,
+    Back,
+}
+
+impl Navigation {
+    /// The step pressed this frame on the keyboard or a controller, if any.
+    pub fn read(gamepads: &Gamepads) -> Option<Self> {
+        let keys = [
+            (KeyCode::Up, Navigation::Up),
+            (KeyCode::Down, Navigation::Down),
+            (KeyCode::Left, Navigation::Left),
+            (KeyCode::Right, Navigation::Right),
+            (KeyCode::Enter, Navigation::Confirm),
+            (KeyCode::KpEnter, Navigation::Confirm),
+            (KeyCode::Space, Navigation::Confirm),
+            (KeyCode::Escape, Navigation::Back),
+        ];
+        let buttons = [
+            (Button::DpadUp, Navigation::Up),
+            (Button::DpadDown, Navigation::Down),
+            (Button::DpadLeft, Navigation::Left),
+            (Button::DpadRight, Navigation::Right),
+            (Button::South, Navigation::Confirm),
+            (Button::Start, Navigation::Confirm),
+            (Button::East, Navigation::Back),
+        ];
+        let key = keys
+            .iter()
+            .find(|(key, _)| is_key_pressed(*key))
+            .map(|&(_, navigation)| navigation);
+        let button = buttons
+            .iter()
+            .find(|(button, _)| gamepads.any_pressed(*button))
+            .map(|&(_, navigation)| navigation);
+        return key.or(button);
+    }
+}
+
+/// Moves `selected` up or down a list of `count` options, wrapping around at the ends.
+pub fn select(selected: &mut usize, count: usize, navigation: Option<Navigation>) {
+    match navigation {
+        Some(Navigation::Up) => *selected = (*selected + count - 1) % count,
+        Some(Navigation::Down) => *selected = (*selected + 1) % count,
+        _ => {}
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TitleOption {
+    Play,
+    HighScores,
+    Settings,
+    Quit,
+}
+
+pub const TITLE_OPTIONS: [TitleOption; 4] = [
+    TitleOption::Play,
+    TitleOption::HighScores,
+    TitleOption::Settings,
+    TitleOption::Quit,
+];
+
+impl TitleOption {
+    pub fn label(self) -> &'static str {
+        match self {
+            TitleOption::Play => "Play",
+            TitleOption::HighScores => "High Scores",
+            TitleOption::Settings => "Settings",
+            TitleOption::Quit => "Quit",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PauseOption {
+    Resume,
+    Restart,
+    KeyBindings,
+    SaveAndQuit,
+    QuitToTitle,
+}
+
+impl PauseOption {
+    /// The options of the pause menu, saving only offered without an opponent.
+    pub fn all(solo: bool) -> Vec<Self> {
+        let mut options = vec![
+            PauseOption::Resume,
+            PauseOption::Restart,
+            PauseOption::KeyBindings,
+        ];
+        if solo {
+            options.push(PauseOption::SaveAndQuit);
+        }
+        options.push(PauseOption::QuitToTitle);
+        return options;
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PauseOption::Resume => "Resume",
+            PauseOption::Restart => "Restart",
+            PauseOption::KeyBindings => "Key Bindings",
+            PauseOption::SaveAndQuit => "Save and Quit",
+            PauseOption::QuitToTitle => "Quit to Title",
+        }
+    }
+}
+
+/// Rows of the settings screen, values changed with left and right.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SettingsOption {
+    Das,
+    Arr,
+    Sdf,
+    ClearDelay,
+    SfxVolume,
+    MusicVolume,
+    Fullscreen,
+    Theme,
+    KeyBindings,
+    Back,
+}
+
+pub const SETTINGS_OPTIONS: [SettingsOption; 10] = [
+    SettingsOption::Das,
+    SettingsOption::Arr,
+    SettingsOption::Sdf,
+    SettingsOption::ClearDelay,
+    SettingsOption::SfxVolume,
+    SettingsOption::MusicVolume,
+    SettingsOption::Fullscreen,
+    SettingsOption::Theme,
+    SettingsOption::KeyBindings,
+    SettingsOption::Back,
+];
+
+impl SettingsOption {
+    pub fn label(self, settings: &Settings, theme: &Theme) -> String {
+        let handling = &settings.handling;
+        match self {
+            SettingsOption::Das => format!("DAS: {} ms", handling.das),
+            SettingsOption::Arr => format!("ARR: {} ms", handling.arr),
+            SettingsOption::Sdf if handling.sdf >= INFINITE_SDF => "SDF: Instant".to_owned(),
+            SettingsOption::Sdf => format!("SDF: {}x", handling.sdf),
+            SettingsOption::ClearDelay if settings.clear_delay => "Line Clear Delay: On".to_owned(),
+            SettingsOption::ClearDelay => "Line Clear Delay: Off".to_owned(),
+            SettingsOption::SfxVolume => volume_label("Sound", settings.sfx_volume, settings),
+            SettingsOption::MusicVolume => volume_label("Music", settings.music_volume, settings),
+            SettingsOption::Fullscreen if settings.fullscreen => "Fullscreen: On".to_owned(),
+            SettingsOption::Fullscreen => "Fullscreen: Off".to_owned(),
+            SettingsOption::Theme => format!("Theme: {}", theme.name),
+            SettingsOption::KeyBindings => "Key Bindings".to_owned(),
+            SettingsOption::Back => "Back".to_owned(),
+        }
+    }
+
+    /// Changes the value of the row by one step, `up` raising it.
+    pub fn adjust(self, settings: &mut Settings, up: bool) {
+        let handling = &mut settings.handling;
+        let step = |value: u64, step: u64, max: u64| {
+            if up {
+                (value + step).min(max)
+            } else {
+                value.saturating_sub(step)
+            }
+        };
+        match self {
+            SettingsOption::Das => handling.das = step(handling.das, DAS_STEP, MAX_DAS),
+            SettingsOption::Arr => handling.arr = step(handling.arr, ARR_STEP, MAX_ARR),
+            SettingsOption::Sdf => {
+                handling.sdf = (step(handling.sdf as u64, 1, INFINITE_SDF as u64) as u32).max(1);
+            }
+            SettingsOption::ClearDelay => settings.clear_delay = up,
+            SettingsOption::SfxVolume => {
+                let volume = step(settings.sfx_volume as u64, VOLUME_STEP as u64, 100);
+                settings.sfx_volume = volume as u32;
+            }
+            SettingsOption::MusicVolume => {
+                let volume = step(settings.music_volume as u64, VOLUME_STEP as u64, 100);
+                settings.music_volume = volume as u32;
+            }
+            SettingsOption::Fullscreen => settings.fullscreen = up,
+            SettingsOption::Theme | SettingsOption::KeyBindings | SettingsOption::Back => {}
+        }
+    }
+}
+
+/// A volume drawn as a bar of ten steps, as in `Music: ######---- 60%`.
+fn volume_label(name: &str, volume: u32, settings: &Settings) -> String {
+    let filled = (volume / 10) as usize;
+    let bar = format!("{}{}", "#".repeat(filled), "-".repeat(10 - filled));
+    if settings.muted {
+        return format!("{}: {} Muted", name, bar);
+    }
+    return format!("{}: {} {}%", name, bar, volume);
+}
@@ -0,0 +1,104 @@
+use crate::config::{Dig, GameConfig};
+use crate::speed::LevelRule;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    Marathon,
+    Sprint,
+    Ultra,
+    Dig,
+    Versus,
+}
+
+/// Every mode, in the order the mode select lists them.
+pub const MODES: [Mode; 5] = [
+    Mode::Marathon,
+    Mode::Sprint,
+    Mode::Ultra,
+    Mode::Dig,
+    Mode::Versus,
+];
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Marathon => "marathon",
+            Mode::Sprint => "sprint",
+            Mode::Ultra => "ultra",
+            Mode::Dig => "dig",
+            Mode::Versus => "versus",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "marathon" => Some(Mode::Marathon),
+            "sprint" => Some(Mode::Sprint),
+            "ultra" => Some(Mode::Ultra),
+            "dig" => Some(Mode::Dig),
+            "versus" => Some(Mode::Versus),
+            _ => None,
+        }
+    }
+
+    /// Name shown in menus and headings.
+    pub fn title(self) -> &'static str {
+        match self {
+            Mode::Marathon => "Marathon",
+            Mode::Sprint => "Sprint",
+            Mode::Ultra => "Ultra",
+            Mode::Dig => "Dig",
+            Mode::Versus => "Versus",
+        }
+    }
+
+    /// One line explaining the goal, shown in the mode select.
+    pub fn description(self) -> &'static str {
+        match self {
+            Mode::Marathon => "Play as long as you can as the speed rises",
+            Mode::Sprint => "Clear 40 lines as fast as you can",
+            Mode::Ultra => "Score as much as you can in two minutes",
+            Mode::Dig => "Dig through 10 rows of garbage",
+            Mode::Versus => "Two players on one keyboard trade garbage",
+        }
+    }
+
+    /// The standard rules of the mode.
+    pub fn config(self) -> GameConfig {
+        match self {
+            Mode::Marathon => GameConfig::default(),
+            Mode::Sprint => GameConfig {
+                mode: self,
+                line_goal: Some(40),
+                level_rule: LevelRule::Fixed,
+                ..GameConfig::default()
+            },
+            Mode::Ultra => GameConfig {
+                mode: self,
+                time_limit: Some(120_000),
+                ..GameConfig::default()
+            },
+            Mode::Dig => GameConfig {
+                mode: self,
+                dig: Some(Dig::default()),
+                level_rule: LevelRule::Fixed,
+                ..GameConfig::default()
+            },
+            Mode::Versus => GameConfig {
+                mode: self,
+                level_rule: LevelRule::Fixed,
+                ..GameConfig::default()
+            },
+        }
+    }
+
+    /// Whether two boards are played against each other.
+    pub fn is_versus(self) -> bool {
+        self == Mode::Versus
+    }
+
+    /// Whether results rank by the time taken rather than by score.
+    pub fn ranks_by_time(self) -> bool {
+        matches!(self, Mode::Sprint | Mode::Dig)
+    }
+}
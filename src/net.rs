@@ -0,0 +1,552 @@
+use crate::config::Handling;
+use crate::global::*;
+use crate::input::{Frame, Input};
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+const MAGIC: &[u8; 4] = b"TTRS";
+pub const PROTOCOL_VERSION: u16 = 3;
+
+const HELLO: u8 = 0;
+const FRAME: u8 = 1;
+const HASH: u8 = 2;
+const PING: u8 = 3;
+const PONG: u8 = 4;
+
+/// Ticks between board hashes compared to detect a desync.
+const HASH_INTERVAL: u32 = 60;
+/// Milliseconds between latency measurements.
+const PING_INTERVAL: u64 = 1000;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Milliseconds without a message after which the other side counts as gone. Pings keep
+/// messages coming even while no frames are sent.
+const PEER_TIMEOUT: u64 = 5000;
+
+/// How to reach the other player, picked on the command line.
+#[derive(Clone, Debug)]
+pub enum Network {
+    Host(u16),
+    Connect(String),
+}
+
+/// What each side tells the other before the match starts.
+#[derive(Clone, Copy, Debug)]
+pub struct Hello {
+    /// Seed of the match, only used from the host.
+    pub seed: u64,
+    /// Handling of the sender, needed to simulate their board the same way they do.
+    pub handling: Handling,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NetError {
+    Disconnected,
+    /// Nothing arrived from the other side for `PEER_TIMEOUT` milliseconds.
+    TimedOut,
+    /// The boards hashed differently on the two sides.
+    Desync,
+    /// The other side speaks another protocol version, or not this protocol at all.
+    Incompatible,
+}
+
+impl NetError {
+    pub fn message(self) -> &'static str {
+        match self {
+            NetError::Disconnected => "Opponent disconnected",
+            NetError::TimedOut => "Opponent stopped responding",
+            NetError::Desync => "Games out of sync",
+            NetError::Incompatible => "Opponent runs an incompatible version",
+        }
+    }
+}
+
+/// A versus match against another machine, kept in lockstep by exchanging every input.
+///
+/// Messages are a little-endian `u16` length followed by a type byte and its fields.
+/// Both sides simulate both boards, advancing a tick only once the input of the other
+/// side for that tick has arrived.
+pub struct Session {
+    listener: Option<TcpListener>,
+    stream: Option<TcpStream>,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    hello: Hello,
+    is_host: bool,
+    pub peer: Option<Hello>,
+    pub error: Option<NetError>,
+
+    local_frames: VecDeque<Frame>,
+    remote_frames: VecDeque<Frame>,
+    tick: u32,
+    local_hashes: VecDeque<(u32, u64)>,
+    remote_hashes: VecDeque<(u32, u64)>,
+
+    last_ping: u64,
+    /// When the last message arrived, or the connection was made.
+    last_heard: Instant,
+    /// Round trip time of the last ping in milliseconds.
+    pub latency: Option<u64>,
+}
+
+impl Session {
+    /// Waits for the other player to connect on `port` of every interface.
+    pub fn host(port: u16, hello: Hello) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        let mut session = Session::new(hello, true);
+        session.listener = Some(listener);
+        return Ok(session);
+    }
+
+    /// Connects to a host at `address`, such as `192.168.1.20:7878`.
+    pub fn connect(address: &str, hello: Hello) -> io::Result<Self> {
+        let address = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "no address found"))?;
+        let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+        let mut session = Session::new(hello, false);
+        session.start(stream)?;
+        return Ok(session);
+    }
+
+    fn new(hello: Hello, is_host: bool) -> Self {
+        Session {
+            listener: None,
+            stream: None,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+            hello,
+            is_host,
+            peer: None,
+            error: None,
+
+            local_frames: VecDeque::new(),
+            remote_frames: VecDeque::new(),
+            tick: 0,
+            local_hashes: VecDeque::new(),
+            remote_hashes: VecDeque::new(),
+
+            last_ping: 0,
+            last_heard: Instant::now(),
+            latency: None,
+        }
+    }
+
+    fn start(&mut self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        self.stream = Some(stream);
+        self.last_heard = Instant::now();
+
+        let mut body = vec![HELLO];
+        body.extend_from_slice(MAGIC);
+        body.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+        body.extend_from_slice(&self.hello.seed.to_le_bytes());
+        body.extend_from_slice(&self.hello.handling.das.to_le_bytes());
+        body.extend_from_slice(&self.hello.handling.arr.to_le_bytes());
+        body.extend_from_slice(&self.hello.handling.sdf.to_le_bytes());
+        self.send(&body);
+        return Ok(());
+    }
+
+    /// Whether both sides have introduced themselves and the match can begin.
+    pub fn is_ready(&self) -> bool {
+        self.peer.is_some() && self.error.is_none()
+    }
+
+    /// The seed both sides play with, chosen by the host.
+    pub fn seed(&self) -> u64 {
+        match (self.is_host, self.peer) {
+            (false, Some(peer)) => peer.seed,
+            _ => self.hello.seed,
+        }
+    }
+
+    /// Accepts the other player, sends what is queued and handles what has arrived.
+    pub fn poll(&mut self) {
+        if self.error.is_some() {
+            return;
+        }
+
+        if let Some(listener) = &self.listener {
+            if let Ok((stream, _)) = listener.accept() {
+                self.listener = None;
+                if self.start(stream).is_err() {
+                    self.error = Some(NetError::Disconnected);
+                    return;
+                }
+            }
+        }
+
+        if self.peer.is_some() && get_millis() - self.last_ping >= PING_INTERVAL {
+            self.last_ping = get_millis();
+            let mut body = vec![PING];
+            body.extend_from_slice(&self.last_ping.to_le_bytes());
+            self.send(&body);
+        }
+
+        self.flush();
+        self.receive();
+
+        // A connection can hang without ever closing, which would stall the match for good.
+        if self.stream.is_some()
+            && self.error.is_none()
+            && self.last_heard.elapsed() >= Duration::from_millis(PEER_TIMEOUT)
+        {
+            self.error = Some(NetError::TimedOut);
+        }
+    }
+
+    /// Queues the input of the local player for the next tick and sends it to the other side.
+    pub fn send_frame(&mut self, frame: Frame) {
+        self.local_frames.push_back(frame);
+
+        let mut body = vec![FRAME];
+        body.extend_from_slice(&(frame.delta.min(u32::MAX as u64) as u32).to_le_bytes());
+        body.push(frame.input.down);
+        body.push(frame.input.pressed);
+        self.send(&body);
+    }
+
+    /// The local and remote frames of the next tick, once both have arrived.
+    pub fn next_tick(&mut self) -> Option<(Frame, Frame)> {
+        if self.error.is_some() || self.local_frames.is_empty() || self.remote_frames.is_empty() {
+            return None;
+        }
+        return Some((
+            self.local_frames.pop_front()?,
+            self.remote_frames.pop_front()?,
+        ));
+    }
+
+    /// Records the hash of both boards after a tick, sending it every `HASH_INTERVAL` ticks.
+    ///
+    /// The hash must not depend on which board is local, as the other side sees them swapped.
+    pub fn end_tick(&mut self, hash: u64) {
+        self.tick += 1;
+        if !self.tick.is_multiple_of(HASH_INTERVAL) {
+            return;
+        }
+
+        self.local_hashes.push_back((self.tick, hash));
+        let mut body = vec![HASH];
+        body.extend_from_slice(&self.tick.to_le_bytes());
+        body.extend_from_slice(&hash.to_le_bytes());
+        self.send(&body);
+        self.compare_hashes();
+    }
+
+    fn compare_hashes(&mut self) {
+        while let (Some(&local), Some(&remote)) =
+            (self.local_hashes.front(), self.remote_hashes.front())
+        {
+            if local != remote {
+                self.error = Some(NetError::Desync);
+                return;
+            }
+            self.local_hashes.pop_front();
+            self.remote_hashes.pop_front();
+        }
+    }
+
+    fn send(&mut self, body: &[u8]) {
+        self.outgoing
+            .extend_from_slice(&(body.len() as u16).to_le_bytes());
+        self.outgoing.extend_from_slice(body);
+    }
+
+    fn flush(&mut self) {
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+
+        while !self.outgoing.is_empty() {
+            match stream.write(&self.outgoing) {
+                Ok(0) => {
+                    self.error = Some(NetError::Disconnected);
+                    return;
+                }
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => return,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.error = Some(NetError::Disconnected);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn receive(&mut self) {
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+
+        let mut buffer = [0; 4096];
+        loop {
+            match stream.read(&mut buffer) {
+                Ok(0) => {
+                    self.error = Some(NetError::Disconnected);
+                    break;
+                }
+                Ok(read) => self.incoming.extend_from_slice(&buffer[..read]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.error = Some(NetError::Disconnected);
+                    break;
+                }
+            }
+        }
+
+        // Messages that arrived before the connection dropped still count.
+        while self.incoming.len() >= 2 {
+            let length = u16::from_le_bytes([self.incoming[0], self.incoming[1]]) as usize;
+            if self.incoming.len() < 2 + length {
+                break;
+            }
+            let body: Vec<u8> = self.incoming.drain(..2 + length).skip(2).collect();
+            self.last_heard = Instant::now();
+            if self.handle(&body).is_none() && self.error.is_none() {
+                self.error = Some(NetError::Incompatible);
+            }
+        }
+    }
+
+    /// Handles one message, returning `None` if it could not be understood.
+    fn handle(&mut self, body: &[u8]) -> Option<()> {
+        let mut reader = Reader { bytes: body };
+        match reader.u8()? {
+            HELLO => {
+                if reader.take(MAGIC.len())? != MAGIC
+                    || u16::from_le_bytes(reader.array()?) != PROTOCOL_VERSION
+                {
+                    return None;
+                }
+                let hello = Hello {
+                    seed: u64::from_le_bytes(reader.array()?),
+                    handling: Handling {
+                        das: u64::from_le_bytes(reader.array()?),
+                        arr: u64::from_le_bytes(reader.array()?),
+                        sdf: u32::from_le_bytes(reader.array()?),
+                    },
+                };
+                if !hello.handling.is_valid() {
+                    return None;
+                }
+                self.peer = Some(hello);
+            }
+            FRAME => {
+                let delta = u32::from_le_bytes(reader.array()?) as u64;
+                let input = Input {
+                    down: reader.u8()?,
+                    pressed: reader.u8()?,
+                };
+                self.remote_frames.push_back(Frame { delta, input });
+            }
+            HASH => {
+                let tick = u32::from_le_bytes(reader.array()?);
+                let hash = u64::from_le_bytes(reader.array()?);
+                self.remote_hashes.push_back((tick, hash));
+                self.compare_hashes();
+            }
+            PING => {
+                let mut reply = vec![PONG];
+                reply.extend_from_slice(reader.take(8)?);
+                self.send(&reply);
+            }
+            PONG => {
+                let sent = u64::from_le_bytes(reader.array()?);
+                self.latency = Some(get_millis().saturating_sub(sent));
+            }
+            _ => return None,
+        }
+        return Some(());
+    }
+}
+
+/// Reads fields off the front of a message body.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < count {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        return Some(taken);
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        return self.take(N)?.try_into().ok();
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        return Some(self.take(1)?[0]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Handling;
+    use crate::game::Game;
+    use crate::input::Action;
+    use crate::mode::Mode;
+    use std::thread;
+
+    fn hello(seed: u64) -> Hello {
+        Hello {
+            seed,
+            handling: Handling::default(),
+        }
+    }
+
+    /// A session waiting on a free port of the loopback interface, and that port.
+    fn host(seed: u64) -> (Session, u16) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut session = Session::new(hello(seed), true);
+        session.listener = Some(listener);
+        return (session, port);
+    }
+
+    #[test]
+    fn silent_peer_times_out() {
+        let (mut host, port) = host(1);
+        // Connects but never says anything.
+        let _silent = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        for _ in 0..100 {
+            host.poll();
+            if host.stream.is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(host.stream.is_some());
+        assert_eq!(host.error, None);
+
+        host.last_heard -= Duration::from_millis(PEER_TIMEOUT);
+        host.poll();
+        assert_eq!(host.error, Some(NetError::TimedOut));
+    }
+
+    /// One side of a match: its session, and its own board then the other side's.
+    struct Side {
+        session: Session,
+        boards: [Game; 2],
+        received: Vec<Frame>,
+    }
+
+    impl Side {
+        fn tick(&mut self, frame: Frame) {
+            self.session.send_frame(frame);
+            self.advance();
+        }
+
+        /// Plays every tick whose frames from both sides have arrived.
+        fn advance(&mut self) {
+            self.session.poll();
+            while let Some((local, remote)) = self.session.next_tick() {
+                self.boards[0].update(&local.input, local.delta);
+                self.boards[1].update(&remote.input, remote.delta);
+                self.received.push(remote);
+                let hash = self.boards[0].state_hash();
+                self.session
+                    .end_tick(hash.wrapping_add(self.boards[1].state_hash()));
+            }
+        }
+    }
+
+    /// Some input for tick `tick` of player `player`, moving and dropping pieces.
+    fn frame(player: u64, tick: u64) -> Frame {
+        let mut input = Input::default();
+        let actions = [
+            Action::Left,
+            Action::Right,
+            Action::RotateClockwise,
+            Action::HardDrop,
+        ];
+        let action = actions[((tick / 7 + player) % actions.len() as u64) as usize];
+        input.set_down(action);
+        if tick.is_multiple_of(7) {
+            input.set_pressed(action);
+        }
+        return Frame {
+            delta: 16 + player,
+            input,
+        };
+    }
+
+    #[test]
+    fn loopback_match_stays_in_sync() {
+        let (mut host, port) = host(42);
+        let mut guest = Session::connect(&format!("127.0.0.1:{}", port), hello(7)).unwrap();
+        for _ in 0..200 {
+            host.poll();
+            guest.poll();
+            if host.is_ready() && guest.is_ready() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(host.is_ready() && guest.is_ready());
+        // The host picks the seed.
+        assert_eq!(guest.seed(), 42);
+
+        let config = Mode::Versus.config();
+        let mut sides = [host, guest].map(|session| Side {
+            boards: [
+                Game::new(config, session.seed()),
+                Game::new(config, session.seed()),
+            ],
+            session,
+            received: Vec::new(),
+        });
+        let ticks = HASH_INTERVAL as u64 * 5;
+        for tick in 0..ticks {
+            for (player, side) in sides.iter_mut().enumerate() {
+                side.tick(frame(player as u64, tick));
+            }
+        }
+        for _ in 0..200 {
+            // Every hash sent has been compared with the other side's once both are done.
+            let done = |side: &Side| {
+                side.received.len() as u64 == ticks && side.session.local_hashes.is_empty()
+            };
+            if sides.iter().all(done) {
+                break;
+            }
+            for side in &mut sides {
+                side.advance();
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        for (player, side) in sides.iter().enumerate() {
+            let other = 1 - player as u64;
+            let expected: Vec<Frame> = (0..ticks).map(|tick| frame(other, tick)).collect();
+            assert_eq!(side.received, expected);
+            assert_eq!(side.session.error, None);
+            assert!(side.session.local_hashes.is_empty());
+        }
+        assert_eq!(
+            sides[0].boards[0].state_hash(),
+            sides[1].boards[1].state_hash()
+        );
+        assert_eq!(
+            sides[0].boards[1].state_hash(),
+            sides[1].boards[0].state_hash()
+        );
+        assert!(sides[0].boards[0].pieces > 0);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::global::*;
+use macroquad::rand::ChooseRandom;
+use macroquad::rand::RandGenerator;
+
+/// Produces the sequence of piece ids dealt to a game.
+pub trait Randomizer {
+    fn next(&mut self) -> usize;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RandomizerKind {
+    Bag7,
+    Bag14,
+    Random,
+    Nes,
+    Tgm,
+}
+
+impl RandomizerKind {
+    pub fn create(self, seed: u64) -> Box<dyn Randomizer> {
+        let rng = RandGenerator::new();
+        rng.srand(seed);
+
+        match self {
+            RandomizerKind::Bag7 => Box::new(Bag::new(rng, 1)),
+            RandomizerKind::Bag14 => Box::new(Bag::new(rng, 2)),
+            RandomizerKind::Random => Box::new(Random { rng }),
+            RandomizerKind::Nes => Box::new(Nes { rng, last: None }),
+            RandomizerKind::Tgm => Box::new(Tgm::new(rng)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RandomizerKind::Bag7 => "7bag",
+            RandomizerKind::Bag14 => "14bag",
+            RandomizerKind::Random => "random",
+            RandomizerKind::Nes => "nes",
+            RandomizerKind::Tgm => "tgm",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "7bag" => Some(RandomizerKind::Bag7),
+            "14bag" => Some(RandomizerKind::Bag14),
+            "random" => Some(RandomizerKind::Random),
+            "nes" => Some(RandomizerKind::Nes),
+            "tgm" => Some(RandomizerKind::Tgm),
+            _ => None,
+        }
+    }
+}
+
+/// Shuffles `copies` of every piece together and deals them out before refilling.
+struct Bag {
+    rng: RandGenerator,
+    copies: usize,
+    bag: Vec<usize>,
+}
+
+impl Bag {
+    fn new(rng: RandGenerator, copies: usize) -> Self {
+        Bag {
+            rng,
+            copies,
+            bag: Vec::new(),
+        }
+    }
+}
+
+impl Randomizer for Bag {
+    fn next(&mut self) -> usize {
+        if self.bag.is_empty() {
+            self.bag = (0..SHAPES.len() * self.copies)
+                .map(|i| i % SHAPES.len())
+                .collect();
+            self.bag.shuffle_with_state(&self.rng);
+        }
+        self.bag.pop().unwrap()
+    }
+}
+
+/// Every piece is drawn independently with equal probability.
+struct Random {
+    rng: RandGenerator,
+}
+
+impl Randomizer for Random {
+    fn next(&mut self) -> usize {
+        self.rng.gen_range(0, SHAPES.len())
+    }
+}
+
+/// Rolls one extra die face as a reroll and rerolls once when the previous piece repeats.
+struct Nes {
+    rng: RandGenerator,
+    last: Option<usize>,
+}
+
+impl Randomizer for Nes {
+    fn next(&mut self) -> usize {
+        let mut id = self.rng.gen_range(0, SHAPES.len() + 1);
+        if id == SHAPES.len() || Some(id) == self.last {
+            id = self.rng.gen_range(0, SHAPES.len());
+        }
+        self.last = Some(id);
+        id
+    }
+}
+
+/// Rerolls up to four times while the piece is in the last four dealt.
+struct Tgm {
+    rng: RandGenerator,
+    history: [usize; 4],
+    first: bool,
+}
+
+impl Tgm {
+    const ROLLS: usize = 4;
+
+    fn new(rng: RandGenerator) -> Self {
+        Tgm {
+            rng,
+            history: [Z_ID; 4],
+            first: true,
+        }
+    }
+}
+
+impl Randomizer for Tgm {
+    fn next(&mut self) -> usize {
+        let mut id = 0;
+        for _ in 0..Self::ROLLS {
+            id = self.rng.gen_range(0, SHAPES.len());
+            let excluded = self.first && matches!(id, S_ID | O_ID | Z_ID);
+            if !excluded && !self.history.contains(&id) {
+                break;
+            }
+        }
+        if self.first && matches!(id, S_ID | O_ID | Z_ID) {
+            id = I_ID;
+        }
+
+        self.first = false;
+        self.history.rotate_left(1);
+        self.history[3] = id;
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [RandomizerKind; 5] = [
+        RandomizerKind::Bag7,
+        RandomizerKind::Bag14,
+        RandomizerKind::Random,
+        RandomizerKind::Nes,
+        RandomizerKind::Tgm,
+    ];
+
+    fn deal(kind: RandomizerKind, seed: u64, count: usize) -> Vec<usize> {
+        let mut randomizer = kind.create(seed);
+        return (0..count).map(|_| randomizer.next()).collect();
+    }
+
+    #[test]
+    fn bags_deal_every_piece_equally() {
+        for (kind, size) in [(RandomizerKind::Bag7, 7), (RandomizerKind::Bag14, 14)] {
+            for seed in 0..20 {
+                for bag in deal(kind, seed, size * 10).chunks(size) {
+                    let mut counts = [0; 7];
+                    for &id in bag {
+                        counts[id] += 1;
+                    }
+                    assert_eq!(counts, [size / 7; 7], "{:?} seed {}", kind, seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_deals_the_same_pieces() {
+        for kind in KINDS {
+            let pieces = deal(kind, 42, 200);
+            assert!(pieces.iter().all(|&id| id < SHAPES.len()), "{:?}", kind);
+            assert_eq!(pieces, deal(kind, 42, 200), "{:?}", kind);
+            assert_ne!(pieces, deal(kind, 43, 200), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn tgm_never_starts_with_s_o_or_z() {
+        for seed in 0..200 {
+            let first = deal(RandomizerKind::Tgm, seed, 1)[0];
+            assert!(![S_ID, O_ID, Z_ID].contains(&first), "seed {}", seed);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use crate::game::Game;
+use crate::storage;
+use std::fs;
+use std::path::PathBuf;
+
+const HEADER: &str = "tetris-records";
+const VERSION: u32 = 1;
+const FILE_NAME: &str = "records.txt";
+
+/// The fastest finished run of a goal, with the time of every split.
+#[derive(Clone, Debug)]
+pub struct PersonalBest {
+    pub time: u64,
+    pub splits: Vec<u64>,
+}
+
+/// Personal bests of goal-based modes, keyed by mode and goal such as `sprint-40` or `dig-10`.
+pub struct Records {
+    path: Option<PathBuf>,
+    entries: Vec<(String, PersonalBest)>,
+}
+
+impl Records {
+    /// Loads the records, starting empty if the file is missing, corrupted or from another version.
+    pub fn load() -> Self {
+        let path = storage::data_dir().map(|dir| dir.join(FILE_NAME));
+        let mut records = Records {
+            path,
+            entries: Vec::new(),
+        };
+
+        let Some(path) = &records.path else {
+            return records;
+        };
+        let Ok(contents) = fs::read_to_string(path) else {
+            return records;
+        };
+
+        let mut lines = contents.lines();
+        if lines.next() != Some(&format!("{} {}", HEADER, VERSION)) {
+            storage::back_up(path);
+            return records;
+        }
+
+        records.entries = lines.filter_map(Self::parse).collect();
+        return records;
+    }
+
+    fn parse(line: &str) -> Option<(String, PersonalBest)> {
+        let mut fields = line.split('\t');
+        let key = fields.next()?.to_owned();
+        let time = fields.next()?.parse().ok()?;
+        let splits = match fields.next() {
+            Some("") | None => Vec::new(),
+            Some(splits) => splits
+                .split(',')
+                .map(|split| split.parse().ok())
+                .collect::<Option<_>>()?,
+        };
+        Some((key, PersonalBest { time, splits }))
+    }
+
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let mut contents = format!("{} {}\n", HEADER, VERSION);
+        for (key, best) in &self.entries {
+            let splits: Vec<String> = best.splits.iter().map(u64::to_string).collect();
+            contents += &format!("{}\t{}\t{}\n", key, best.time, splits.join(","));
+        }
+        let _ = fs::write(path, contents);
+    }
+
+    /// The key the records of `game` are kept under, if it has a goal and is otherwise
+    /// played by the standard rules of its mode. Other rules would make the times of a key
+    /// incomparable, so such games have no records.
+    pub fn key(game: &Game) -> Option<String> {
+        let goal = game
+            .config
+            .line_goal
+            .or(game.config.dig.map(|dig| dig.goal))?;
+
+        let rules = game.config.mode.config();
+        let mut config = game.config;
+        config.line_goal = rules.line_goal;
+        if let (Some(dig), Some(standard)) = (&mut config.dig, rules.dig) {
+            dig.goal = standard.goal;
+        }
+        if !config.is_standard() {
+            return None;
+        }
+        Some(format!("{}-{}", game.config.mode.name(), goal))
+    }
+
+    pub fn get(&self, game: &Game) -> Option<&PersonalBest> {
+        let key = Self::key(game)?;
+        self.entries
+            .iter()
+            .find(|(other, _)| *other == key)
+            .map(|(_, best)| best)
+    }
+
+    /// Records a finished run if it beats the personal best, returning whether it did.
+    pub fn submit(&mut self, game: &Game) -> bool {
+        let Some(key) = Self::key(game) else {
+            return false;
+        };
+        if !game.finished || self.get(game).is_some_and(|best| best.time <= game.time) {
+            return false;
+        }
+
+        let best = PersonalBest {
+            time: game.time,
+            splits: game.splits.clone(),
+        };
+        self.entries.retain(|(other, _)| *other != key);
+        self.entries.push((key, best));
+        return true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+    use crate::mode::Mode;
+    use crate::randomizer::RandomizerKind;
+
+    fn key(config: GameConfig) -> Option<String> {
+        return Records::key(&Game::new(config, 1));
+    }
+
+    #[test]
+    fn keys_follow_the_goal_but_not_other_rules() {
+        let sprint = Mode::Sprint.config();
+        assert_eq!(key(sprint).as_deref(), Some("sprint-40"));
+        let goal = GameConfig {
+            line_goal: Some(100),
+            ..sprint
+        };
+        assert_eq!(key(goal).as_deref(), Some("sprint-100"));
+        let mut dig = Mode::Dig.config();
+        dig.dig.as_mut().unwrap().goal = 25;
+        assert_eq!(key(dig).as_deref(), Some("dig-25"));
+
+        assert_eq!(key(Mode::Marathon.config()), None);
+        let preview = GameConfig {
+            preview_count: 1,
+            ..sprint
+        };
+        assert_eq!(key(preview), None);
+        let random = GameConfig {
+            randomizer: RandomizerKind::Random,
+            ..goal
+        };
+        assert_eq!(key(random), None);
+    }
+}
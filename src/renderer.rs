@@ -1,202 +1,759 @@
 use crate::assets::Assets;
+use crate::controls::*;
+use crate::effects::Effects;
+use crate::event::Event;
 use crate::game::Game;
 use crate::global::*;
+use crate::highscores::Entry;
+use crate::layout::Layout;
+use crate::mode::Mode;
+use crate::records::PersonalBest;
 use crate::tetromino::Tetromino;
+use crate::theme::{self, BlockStyle, GhostStyle, Theme};
 use macroquad::prelude::*;
 
 pub struct Renderer<'a> {
     assets: &'a Assets,
-    controls: &'a Controls,
-    pub paused: bool,
+    popup: Option<(String, f64)>,
+    pub personal_best: Option<PersonalBest>,
+    /// Grid position of the top left corner, moving the second board of versus aside.
+    pub origin: Vec2,
+    pub layout: Layout,
+    pub theme: &'a Theme,
+    effects: Effects,
 }
 
 impl<'a> Renderer<'a> {
-    pub fn new(assets: &'a Assets, controls: &'a Controls) -> Self {
+    pub fn new(assets: &'a Assets, theme: &'a Theme) -> Self {
         Self {
             assets,
-            controls,
-            paused: false,
+            popup: None,
+            personal_best: None,
+            origin: Vec2::ZERO,
+            layout: Layout::current(1),
+            theme,
+            effects: Effects::default(),
         }
     }
 
-    pub fn update(&mut self) {
-        if is_key_pressed(self.controls.pause) {
-            self.paused = !self.paused;
+    /// Shows `label` where clear names appear, split over two lines at its last space.
+    pub fn show_popup(&mut self, label: &str) {
+        self.popup = Some((label.to_owned(), get_time()));
+    }
+
+    pub fn handle_events(&mut self, events: &[Event]) {
+        self.effects.expire();
+        for event in events {
+            if let Some(label) = event.label() {
+                self.popup = Some((label, get_time()));
+            }
+            self.effects.handle(event);
         }
     }
 
-    pub fn draw(&self, game: &Game) {
-        self.draw_left_panel(&game);
-        self.draw_center_panel(&game);
-        self.draw_right_panel(&game);
-        self.draw_paused();
+    pub fn draw(&self, game: &Game, controls: &Controls) {
+        self.draw_left_panel(game, controls);
+        self.draw_center_panel(game);
+        self.draw_right_panel(game);
     }
 
-    fn draw_left_panel(&self, game: &Game) {
+    fn draw_left_panel(&self, game: &Game, controls: &Controls) {
         let x_text = GAME_SIDE_WIDTH / 2.0;
         let y_text = 2.0;
 
         self.draw_text("Hold:", FONT_SIZE, Vec2::new(x_text, y_text), true);
 
         if game.empty_hold {
-            self.draw_text("Press C.", SMALL_FONT_SIZE, Vec2::new(x_text, 5.0), true);
+            if let Some(&key) = controls.hold.first() {
+                let hint = format!("Press {}.", key_label(key));
+                self.draw_text(&hint, SMALL_FONT_SIZE, Vec2::new(x_text, 5.0), true);
+            }
         } else {
-            self.draw_tetromino(game.hold, false, Vec2::new(x_text, 3.5));
+            self.draw_tetromino(game.hold, false, Vec2::new(x_text, 3.5), 1.0);
         }
-    }
 
-    fn draw_center_panel(&self, game: &Game) {
-        for y in 0..BOARD_HEIGHT as usize {
-            for x in 0..BOARD_WIDTH as usize {
-                Self::draw_block(
-                    Vec2::new(x as f32 + GAME_SIDE_WIDTH, y as f32),
-                    game.board[y][x],
-                    false,
-                );
+        if let Some((label, start)) = &self.popup {
+            if get_time() - start < POPUP_DURATION {
+                let (first, second) = label.rsplit_once(' ').unwrap_or((label, ""));
+                self.draw_text(first, SMALL_FONT_SIZE, Vec2::new(x_text, 7.0), true);
+                self.draw_text(second, SMALL_FONT_SIZE, Vec2::new(x_text, 7.8), true);
             }
         }
 
-        self.draw_tetromino(game.piece, false, Vec2::ZERO);
-        self.draw_tetromino(game.phantom, true, Vec2::ZERO);
-    }
+        if game.combo > 0 {
+            let combo = format!("Combo {}", game.combo);
+            self.draw_text(&combo, SMALL_FONT_SIZE, Vec2::new(x_text, 9.0), true);
+        }
+        if game.back_to_back > 0 {
+            let back_to_back = format!("B2B x{}", game.back_to_back);
+            self.draw_text(&back_to_back, SMALL_FONT_SIZE, Vec2::new(x_text, 9.8), true);
+        }
 
-    fn draw_right_panel(&self, game: &Game) {
-        let x_text = GAME_SIDE_WIDTH + BOARD_WIDTH as f32 + GAME_SIDE_WIDTH / 2.0;
-        let y_text = BOARD_HEIGHT as f32 - 2.0;
+        if let Some(goal) = game.config.line_goal {
+            self.draw_goal_stats(game, goal, x_text);
+            return;
+        }
+        if let Some(dig) = game.config.dig {
+            self.draw_dig_stats(game, dig.goal, x_text);
+            return;
+        }
+        if let Some(limit) = game.config.time_limit {
+            self.draw_countdown_stats(game, limit, x_text);
+            return;
+        }
 
-        self.draw_text("Next:", FONT_SIZE, Vec2::new(x_text, 2.0), true);
-        self.draw_text("Score", FONT_SIZE, Vec2::new(x_text, y_text - 8.5), true);
+        let stats = [
+            ("Score", game.score),
+            ("Lines", game.lines),
+            ("Level", game.level),
+        ];
+        for (i, (label, value)) in stats.iter().enumerate() {
+            let y_stat = 11.5 + i as f32 * 2.5;
+            self.draw_text(label, FONT_SIZE, Vec2::new(x_text, y_stat), true);
+            self.draw_text(
+                &value.to_string(),
+                FONT_SIZE,
+                Vec2::new(x_text, y_stat + 1.2),
+                true,
+            );
+        }
+    }
+
+    /// Timer, line count and splits of a game played to a line goal, with every split
+    /// compared against the personal best.
+    fn draw_goal_stats(&self, game: &Game, goal: u32, x_text: f32) {
+        self.draw_text("Time", FONT_SIZE, Vec2::new(x_text, 11.5), true);
         self.draw_text(
-            &game.score.to_string(),
+            &format_time(game.time),
             FONT_SIZE,
-            Vec2::new(x_text, y_text - 7.0),
+            Vec2::new(x_text, 12.7),
             true,
         );
-        self.draw_text("Lines", FONT_SIZE, Vec2::new(x_text, y_text - 5.0), true);
+        self.draw_text("Lines", FONT_SIZE, Vec2::new(x_text, 14.0), true);
         self.draw_text(
-            &game.lines.to_string(),
+            &format!("{} / {}", game.lines.min(goal), goal),
             FONT_SIZE,
-            Vec2::new(x_text, y_text - 3.5),
+            Vec2::new(x_text, 15.2),
             true,
         );
-        self.draw_text("Level", FONT_SIZE, Vec2::new(x_text, y_text - 1.5), true);
-        self.draw_text(
-            &game.level.to_string(),
+
+        // Long goals have more splits than the panel has room for, so only the latest show.
+        let first = game.splits.len().saturating_sub(SPLIT_ROWS);
+        for (row, (i, &split)) in game.splits.iter().enumerate().skip(first).enumerate() {
+            let best = self
+                .personal_best
+                .as_ref()
+                .and_then(|best| best.splits.get(i));
+            let line = match best {
+                Some(&best) => format!("{} {}", format_time(split), format_difference(split, best)),
+                None => format_time(split),
+            };
+            let pos = Vec2::new(x_text, SPLITS_TOP + row as f32 * SPLIT_SPACING);
+            self.draw_text(&line, SMALL_FONT_SIZE, pos, true);
+        }
+    }
+
+    /// Timer and garbage rows left to dig out.
+    fn draw_dig_stats(&self, game: &Game, goal: u32, x_text: f32) {
+        let stats = [
+            ("Time", format_time(game.time)),
+            (
+                "Garbage",
+                goal.saturating_sub(game.garbage_cleared).to_string(),
+            ),
+            ("Pieces", game.pieces.to_string()),
+        ];
+        for (i, (label, value)) in stats.iter().enumerate() {
+            let y_stat = 11.5 + i as f32 * 2.5;
+            self.draw_text(label, FONT_SIZE, Vec2::new(x_text, y_stat), true);
+            self.draw_text(value, FONT_SIZE, Vec2::new(x_text, y_stat + 1.2), true);
+        }
+    }
+
+    /// Time left, turning red in the final seconds, above the score and line count of a
+    /// game played against the clock.
+    fn draw_countdown_stats(&self, game: &Game, limit: u64, x_text: f32) {
+        let remaining = limit.saturating_sub(game.time);
+        let color = if remaining <= COUNTDOWN_SECONDS * 1000 {
+            self.theme.warning
+        } else {
+            self.theme.text
+        };
+        self.draw_text("Time", FONT_SIZE, Vec2::new(x_text, 11.5), true);
+        self.draw_colored_text(
+            &format_time(remaining),
             FONT_SIZE,
-            Vec2::new(x_text, y_text),
+            Vec2::new(x_text, 12.7),
             true,
+            color,
         );
 
-        self.draw_tetromino(game.preview, false, Vec2::new(x_text, 3.5));
+        let stats = [("Score", game.score), ("Lines", game.lines)];
+        for (i, (label, value)) in stats.iter().enumerate() {
+            let y_stat = 14.0 + i as f32 * 2.5;
+            self.draw_text(label, FONT_SIZE, Vec2::new(x_text, y_stat), true);
+            self.draw_text(
+                &value.to_string(),
+                FONT_SIZE,
+                Vec2::new(x_text, y_stat + 1.2),
+                true,
+            );
+        }
     }
 
-    fn draw_paused(&self) {
-        if self.paused {
+    fn draw_center_panel(&self, game: &Game) {
+        // Empty cells stay put while the blocks above cleared rows fall into place.
+        for y in 0..BOARD_HEIGHT {
+            for x in 0..BOARD_WIDTH {
+                let pos = Vec2::new(x as f32 + GAME_SIDE_WIDTH, y as f32);
+                self.draw_block(pos, BOARD_COLOR, false, 1.0);
+            }
+        }
+        for y in 0..BOARD_HEIGHT {
+            let offset = self.effects.row_offset(y);
+            for x in 0..BOARD_WIDTH {
+                let pos = Vec2::new(x as f32 + GAME_SIDE_WIDTH, y as f32 - offset);
+                if game.board[y][x] != BOARD_COLOR && pos.y > -1.0 {
+                    self.draw_block(pos, game.board[y][x], false, 1.0);
+                }
+            }
+        }
+        self.draw_effects();
+
+        if !game.is_waiting() {
+            self.draw_tetromino(game.piece, false, Vec2::ZERO, 1.0);
+            self.draw_tetromino(game.phantom, true, Vec2::ZERO, 1.0);
+        }
+        self.draw_particles();
+
+        let pending = game.pending_garbage().min(BOARD_HEIGHT as u32) as f32;
+        if pending > 0.0 {
+            let pos = self.screen_pos(Vec2::new(
+                GAME_SIDE_WIDTH - 0.3,
+                BOARD_HEIGHT as f32 - pending,
+            ));
             draw_rectangle(
-                0.0,
-                0.0,
-                screen_width(),
-                screen_height(),
-                Color::new(0.0, 0.0, 0.0, 0.95),
+                pos.x,
+                pos.y,
+                self.layout.block * 0.25,
+                pending * self.layout.block,
+                get_color(self.theme.warning, 255),
             );
+        }
+    }
 
-            let center = Vec2::new(screen_width() * 0.5, screen_height() * 0.25);
-            self.draw_text("Pause", FONT_SIZE, center, false);
+    /// Draws the flashes of locked pieces and cleared rows, and hard drop trails.
+    fn draw_effects(&self) {
+        for (cells, alpha) in self.effects.lock_flashes() {
+            for &(x, y) in cells {
+                let pos = Vec2::new(
+                    x as f32 + GAME_SIDE_WIDTH,
+                    y as f32 - self.effects.row_offset(y.max(0) as usize),
+                );
+                self.draw_overlay_rect(pos, Vec2::ONE, WHITE, alpha * 0.6);
+            }
+        }
 
-            let controls_start = Vec2::new(screen_width() * 0.5, screen_height() * 0.45);
-            let line_spacing = 50.0;
+        if let Some((rows, alpha)) = self.effects.flash() {
+            for y in (0..BOARD_HEIGHT).filter(|&y| rows & 1 << y != 0) {
+                let pos = Vec2::new(GAME_SIDE_WIDTH, y as f32);
+                self.draw_overlay_rect(pos, Vec2::new(BOARD_WIDTH as f32, 1.0), WHITE, alpha);
+            }
+        }
 
-            let controls = [
-                "↑ - Rotate Clockwise",
-                "← / → - Move Left / Right",
-                "↓ - Soft Drop",
-                "Space - Hard Drop",
-                "Ctrl - Rotate Counterclockwise",
-                "C - Hold",
-            ];
+        let now = get_time();
+        for trail in &self.effects.trails {
+            let color = get_color(self.theme.pieces[trail.piece.id], 255);
+            let cells: Vec<(i32, i32)> = trail.piece.cells().collect();
+            // One streak per column, above the highest block of the piece in it.
+            for &(x, y) in &cells {
+                if cells
+                    .iter()
+                    .any(|&(other_x, other_y)| other_x == x && other_y < y)
+                {
+                    continue;
+                }
+                let top = (y - trail.rows as i32).max(0);
+                let pos = Vec2::new(x as f32 + GAME_SIDE_WIDTH + 0.15, top as f32);
+                let size = Vec2::new(0.7, (y - top) as f32);
+                self.draw_overlay_rect(pos, size, color, trail.alpha(now) * 0.35);
+            }
+        }
+    }
+
+    fn draw_particles(&self) {
+        let now = get_time();
+        for particle in &self.effects.particles {
+            let pos = particle.pos(now) + Vec2::new(GAME_SIDE_WIDTH - 0.1, -0.1);
+            let color = get_color(self.theme.pieces[particle.piece], 255);
+            self.draw_overlay_rect(pos, Vec2::splat(0.2), color, particle.alpha(now));
+        }
+    }
+
+    /// Fills `size` blocks from grid position `pos` in `color` at opacity `alpha`.
+    fn draw_overlay_rect(&self, pos: Vec2, size: Vec2, color: Color, alpha: f32) {
+        let screen = self.screen_pos(pos);
+        let size = size * self.layout.block;
+        let color = Color { a: alpha, ..color };
+        draw_rectangle(screen.x, screen.y, size.x, size.y, color);
+    }
+
+    fn draw_right_panel(&self, game: &Game) {
+        let x_text = GAME_SIDE_WIDTH + BOARD_WIDTH as f32 + GAME_SIDE_WIDTH / 2.0;
+
+        if !game.queue().is_empty() {
+            self.draw_text("Next:", FONT_SIZE, Vec2::new(x_text, 2.0), true);
+        }
+        // Long queues shrink to fit the column rather than run into what is below it.
+        let count = game.queue().len().max(1) as f32;
+        let spacing = PREVIEW_SPACING.min(PREVIEW_HEIGHT / count);
+        let scale = spacing / PREVIEW_SPACING;
+        for (i, &next) in game.queue().iter().enumerate() {
+            let pos = Vec2::new(x_text, 3.5 + i as f32 * spacing);
+            self.draw_tetromino(next, false, pos, scale);
+        }
+    }
+
+    /// Draws the playback speed and position of a replay below the next queue.
+    pub fn draw_replay_status(&self, speed: f64, paused: bool, time: u64, duration: u64) {
+        let x_text = GAME_SIDE_WIDTH + BOARD_WIDTH as f32 + GAME_SIDE_WIDTH / 2.0;
+        let state = if paused {
+            "Paused".to_owned()
+        } else {
+            format!("Replay {}x", speed)
+        };
+        let position = format!("{} / {}", format_time(time), format_time(duration));
+        self.draw_text(&state, SMALL_FONT_SIZE, Vec2::new(x_text, 18.5), true);
+        self.draw_text(&position, SMALL_FONT_SIZE, Vec2::new(x_text, 19.3), true);
+    }
+
+    /// Draws the title screen with the option at `selected` highlighted.
+    pub fn draw_title(&self, options: &[String], selected: usize) {
+        let center = self.layout.relative(0.5, 0.25);
+        self.draw_text("TETRIS", TITLE_FONT_SIZE, center, false);
+        self.draw_options(options, selected, 0.45);
+    }
+
+    /// Draws a menu titled `title` on a dimmed screen, with `note` below the options.
+    pub fn draw_menu(&self, title: &str, options: &[String], selected: usize, note: Option<&str>) {
+        Self::draw_overlay(0.95);
+
+        let center = self.layout.relative(0.5, 0.15);
+        self.draw_text(title, FONT_SIZE, center, false);
+        self.draw_options(options, selected, 0.3);
+
+        if let Some(note) = note {
+            let pos = self.layout.relative(0.5, 0.85);
+            self.draw_text(note, SMALL_FONT_SIZE, pos, false);
+        }
+    }
+
+    /// Draws the pause menu over the game, listing the controls above its options.
+    pub fn draw_pause(
+        &self,
+        game: &Game,
+        controls: &Controls,
+        options: &[String],
+        selected: usize,
+    ) {
+        Self::draw_overlay(0.95);
+
+        let center = self.layout.relative(0.5, 0.1);
+        self.draw_text("Pause", FONT_SIZE, center, false);
+
+        let controls_start = self.layout.relative(0.5, 0.2);
+        for i in 0..CONTROL_COUNT {
+            let pos = controls_start + Vec2::new(0.0, i as f32 * 36.0 * self.layout.scale());
+            self.draw_text(&controls.help_line(i), SMALL_FONT_SIZE, pos, false);
+        }
 
-            for (i, &line) in controls.iter().enumerate() {
-                let pos = controls_start + Vec2::new(0.0, i as f32 * line_spacing);
+        self.draw_options(options, selected, 0.6);
+
+        let seed_pos = self.layout.relative(0.5, 0.94);
+        let seed = format!("Seed: {}", game.seed());
+        self.draw_text(&seed, SMALL_FONT_SIZE, seed_pos, false);
+    }
+
+    /// Lists `options` from `top`, a fraction of the screen height, down.
+    fn draw_options(&self, options: &[String], selected: usize, top: f32) {
+        let start = self.layout.relative(0.5, top);
+        for (i, option) in options.iter().enumerate() {
+            let pos = start + Vec2::new(0.0, i as f32 * 45.0 * self.layout.scale());
+            if i == selected {
+                let line = format!("> {} <", option);
+                self.draw_colored_text(&line, FONT_SIZE, pos, false, self.theme.selected);
+            } else {
+                self.draw_text(option, SMALL_FONT_SIZE, pos, false);
+            }
+        }
+    }
+
+    /// Draws the rebinding screen titled `title` with row `selected` highlighted.
+    pub fn draw_bindings<B: Binding>(
+        &self,
+        title: &str,
+        controls: &Controls<B>,
+        selected: usize,
+        waiting: bool,
+    ) {
+        Self::draw_overlay(0.95);
+
+        let center = self.layout.relative(0.5, 0.1);
+        self.draw_text(title, FONT_SIZE, center, false);
+
+        let rows_start = self.layout.relative(0.5, 0.22);
+        for i in 0..CONTROL_COUNT {
+            let pos = rows_start + Vec2::new(0.0, i as f32 * 50.0 * self.layout.scale());
+            let mut line = controls.help_line(i);
+            if i == selected {
+                line = if waiting {
+                    format!("> Press a key or button for {} <", controls.entry(i).1)
+                } else {
+                    format!("> {} <", line)
+                };
+            }
+            self.draw_text(&line, SMALL_FONT_SIZE, pos, false);
+        }
+
+        let help = [
+            "Enter - Add Key",
+            "Backspace - Clear",
+            "R - Reset All",
+            "Tab - Next Player or Controller",
+            "Esc - Back",
+        ];
+        let help_start = self.layout.relative(0.5, 0.72);
+        for (i, line) in help.iter().enumerate() {
+            let pos = help_start + Vec2::new(0.0, i as f32 * 40.0 * self.layout.scale());
+            self.draw_text(line, SMALL_FONT_SIZE, pos, false);
+        }
+    }
+
+    /// Draws the results of a finished game, prompting for a name while `name` is set.
+    pub fn draw_results(&self, game: &Game, name: Option<&str>) {
+        Self::draw_overlay(0.85);
+
+        let center = self.layout.relative(0.5, 0.2);
+        let title = if game.finished {
+            "Finished"
+        } else {
+            "Game Over"
+        };
+        self.draw_text(title, FONT_SIZE, center, false);
+
+        let mut results = vec![
+            format!("Score: {}", game.score),
+            format!("Lines: {}", game.lines),
+            format!("Level: {}", game.level),
+            format!("Time: {}", format_time(game.time)),
+            format!("Pieces: {}", game.pieces),
+            format!("PPS: {:.2}", game.pps()),
+        ];
+        if let Some(best) = &self.personal_best {
+            if game.finished && best.time == game.time {
+                results.push("New personal best!".to_owned());
+            } else {
+                results.push(format!("Best: {}", format_time(best.time)));
+            }
+        }
+        let results_start = self.layout.relative(0.5, 0.33);
+        for (i, line) in results.iter().enumerate() {
+            let pos = results_start + Vec2::new(0.0, i as f32 * 45.0 * self.layout.scale());
+            self.draw_text(line, SMALL_FONT_SIZE, pos, false);
+        }
+
+        let options_start = self.layout.relative(0.5, 0.75);
+        let options = match name {
+            Some(name) => vec![
+                "New high score!".to_owned(),
+                format!("Name: {}_", name),
+                "Enter - Confirm".to_owned(),
+            ],
+            None => vec![
+                "Enter - Retry".to_owned(),
+                "H - High Scores".to_owned(),
+                "Esc - Title".to_owned(),
+            ],
+        };
+        for (i, line) in options.iter().enumerate() {
+            let pos = options_start + Vec2::new(0.0, i as f32 * 45.0 * self.layout.scale());
+            self.draw_text(line, SMALL_FONT_SIZE, pos, false);
+        }
+    }
+
+    /// Draws who won a versus match and how each player did, with `note` explaining an
+    /// early end and a rematch offered unless `rematch` is false.
+    pub fn draw_versus_results(&self, games: [&Game; 2], note: Option<&str>, rematch: bool) {
+        Self::draw_overlay(0.85);
+
+        let lost = games.map(|game| game.top_out.is_some());
+        let title = match lost {
+            [true, false] => "Player 2 Wins",
+            [false, true] => "Player 1 Wins",
+            _ => "Draw",
+        };
+        let center = self.layout.relative(0.5, 0.2);
+        self.draw_text(title, FONT_SIZE, center, false);
+        if let Some(note) = note {
+            let pos = self.layout.relative(0.5, 0.26);
+            self.draw_text(note, SMALL_FONT_SIZE, pos, false);
+        }
+
+        for (player, game) in games.iter().enumerate() {
+            let start = self.layout.relative(0.3 + player as f32 * 0.4, 0.33);
+            let results = [
+                format!("Player {}", player + 1),
+                format!("Lines: {}", game.lines),
+                format!("Sent: {}", game.lines_sent),
+                format!("Pieces: {}", game.pieces),
+                format!("PPS: {:.2}", game.pps()),
+            ];
+            for (i, line) in results.iter().enumerate() {
+                let pos = start + Vec2::new(0.0, i as f32 * 45.0 * self.layout.scale());
                 self.draw_text(line, SMALL_FONT_SIZE, pos, false);
             }
         }
+
+        let options: &[&str] = if rematch {
+            &["Enter - Rematch", "Esc - Title"]
+        } else {
+            &["Esc - Quit"]
+        };
+        let options_start = self.layout.relative(0.5, 0.75);
+        for (i, line) in options.iter().enumerate() {
+            let pos = options_start + Vec2::new(0.0, i as f32 * 45.0 * self.layout.scale());
+            self.draw_text(line, SMALL_FONT_SIZE, pos, false);
+        }
+    }
+
+    /// Draws `lines` in the middle of a dimmed screen, above a way out.
+    pub fn draw_message(&self, lines: &[String]) {
+        Self::draw_overlay(0.95);
+
+        let start = self.layout.relative(0.5, 0.4);
+        for (i, line) in lines.iter().enumerate() {
+            let pos = start + Vec2::new(0.0, i as f32 * 45.0 * self.layout.scale());
+            self.draw_text(line, SMALL_FONT_SIZE, pos, false);
+        }
+
+        let pos = self.layout.relative(0.5, 0.9);
+        self.draw_text("Esc - Quit", SMALL_FONT_SIZE, pos, false);
+    }
+
+    /// Draws a short line of status text at the bottom of the screen.
+    pub fn draw_status(&self, text: &str) {
+        let pos = self.layout.relative(0.5, 1.0) - Vec2::new(0.0, 20.0 * self.layout.scale());
+        self.draw_text(text, SMALL_FONT_SIZE, pos, false);
+    }
+
+    /// Draws the leaderboard of `mode`, marking the entry at `highlight`.
+    pub fn draw_high_scores(&self, mode: Mode, entries: &[&Entry], highlight: Option<usize>) {
+        Self::draw_overlay(0.95);
+
+        let center = self.layout.relative(0.5, 0.1);
+        let title = format!("High Scores - {}", mode.title());
+        self.draw_text(&title, FONT_SIZE, center, false);
+
+        if entries.is_empty() {
+            let pos = self.layout.relative(0.5, 0.5);
+            self.draw_text("No results yet.", SMALL_FONT_SIZE, pos, false);
+        }
+
+        let columns = [0.06, 0.22, 0.44, 0.6, 0.75, 0.9];
+        let header = ["#", "Name", "Score", "Lines", "Time", "Date"];
+        for (cell, column) in header.iter().zip(columns) {
+            let pos = self.layout.relative(column, 0.18);
+            self.draw_text(cell, SMALL_FONT_SIZE, pos, false);
+        }
+
+        let rows_start = self.layout.relative(0.0, 0.25).y;
+        for (i, entry) in entries.iter().enumerate() {
+            let y = rows_start + i as f32 * 60.0 * self.layout.scale();
+            let marker = if highlight == Some(i) { "> " } else { "" };
+            let cells = [
+                format!("{}{}.", marker, i + 1),
+                if entry.name.is_empty() {
+                    "-".to_owned()
+                } else {
+                    entry.name.clone()
+                },
+                entry.score.to_string(),
+                entry.lines.to_string(),
+                format_time(entry.time),
+                format_date(entry.date),
+            ];
+            for (cell, column) in cells.iter().zip(columns) {
+                let pos = Vec2::new(self.layout.relative(column, 0.0).x, y);
+                self.draw_text(cell, SMALL_FONT_SIZE, pos, false);
+            }
+        }
+
+        let pos = self.layout.relative(0.5, 0.95);
+        self.draw_text("←/→ - Mode    Enter - Back", SMALL_FONT_SIZE, pos, false);
+    }
+
+    /// Fills the canvas with the background, leaving the letterbox bars around it.
+    pub fn draw_background(&self) {
+        let Layout { offset, size, .. } = self.layout;
+        let color = get_color(self.theme.background, 255);
+        draw_rectangle(offset.x, offset.y, size.x, size.y, color);
+        if let Some(image) = &self.theme.background_image {
+            let params = DrawTextureParams {
+                dest_size: Some(size),
+                ..Default::default()
+            };
+            draw_texture_ex(image, offset.x, offset.y, WHITE, params);
+        }
+    }
+
+    fn draw_overlay(alpha: f32) {
+        draw_rectangle(
+            0.0,
+            0.0,
+            screen_width(),
+            screen_height(),
+            Color::new(0.0, 0.0, 0.0, alpha),
+        );
     }
 
     fn draw_text(&self, text: &str, font_size: u16, pos: Vec2, grid: bool) {
+        self.draw_colored_text(text, font_size, pos, grid, self.theme.text);
+    }
+
+    fn draw_colored_text(
+        &self,
+        text: &str,
+        font_size: u16,
+        pos: Vec2,
+        grid: bool,
+        color: (u8, u8, u8),
+    ) {
         let mut calc_pos = pos;
         if grid {
-            calc_pos = get_pos(pos);
+            calc_pos = self.screen_pos(pos);
         }
-        calc_pos -= get_text_center(&text, Some(&self.assets.font), font_size, 1.0, 0.0);
+        // Glyphs are rasterized at the size they end up on screen, in pixels rather than
+        // points on HiDPI displays, so text stays sharp however far the layout is scaled.
+        let (font_size, font_scale, font_scale_aspect) =
+            camera_font_scale(font_size as f32 * self.layout.scale());
+        calc_pos -= get_text_center(text, Some(self.font()), font_size, font_scale, 0.0);
         draw_text_ex(
-            &text,
+            text,
             calc_pos.x,
             calc_pos.y,
             TextParams {
-                font_size: font_size,
-                font: Some(&self.assets.font),
-                color: get_color(FONT_COLOR, 255),
+                font_size,
+                font_scale,
+                font_scale_aspect,
+                font: Some(self.font()),
+                color: get_color(color, 255),
                 ..Default::default()
             },
         );
     }
 
-    fn draw_tetromino(&self, tetromino: Tetromino, phantom: bool, ui_grid_pos: Vec2) {
+    fn font(&self) -> &Font {
+        return self.theme.font.as_ref().unwrap_or(&self.assets.font);
+    }
+
+    /// Draws a piece on the board, or centered on `ui_grid_pos` in a panel with blocks
+    /// `scale` times the usual size.
+    fn draw_tetromino(&self, tetromino: Tetromino, phantom: bool, ui_grid_pos: Vec2, scale: f32) {
         for y in 0..4 {
             for x in 0..4 {
                 if tetromino.shape()[y][x] {
-                    let mut tetromino_pos = tetromino.pos + Vec2::new(GAME_SIDE_WIDTH, 0.0);
-                    if ui_grid_pos != Vec2::ZERO {
-                        tetromino_pos = Vec2::new(tetromino.ui_offset(), 0.0) + ui_grid_pos;
-                    }
-                    tetromino_pos += Vec2::new(x as f32, y as f32);
-                    Self::draw_block(tetromino_pos, tetromino.color, phantom);
+                    let block = Vec2::new(x as f32, y as f32);
+                    let tetromino_pos = if ui_grid_pos != Vec2::ZERO {
+                        ui_grid_pos + (Vec2::new(tetromino.ui_offset(), 0.0) + block) * scale
+                    } else {
+                        tetromino.pos + Vec2::new(GAME_SIDE_WIDTH, 0.0) + block
+                    };
+                    self.draw_block(tetromino_pos, tetromino.color, phantom, scale);
                 }
             }
         }
     }
 
-    fn draw_block(grid_pos: Vec2, color: (u8, u8, u8), phantom: bool) {
-        let pos = get_pos(grid_pos);
+    fn screen_pos(&self, grid_pos: Vec2) -> Vec2 {
+        return self
+            .layout
+            .grid_pos(self.origin + grid_pos + self.effects.shake());
+    }
+
+    /// Draws a block at `grid_pos`, `scale` times the size of those on the board.
+    fn draw_block(&self, grid_pos: Vec2, cell: (u8, u8, u8), phantom: bool, scale: f32) {
+        let pos = self.screen_pos(grid_pos);
+        let color = self.theme.color(cell);
         let color_var = get_color(color, 255);
+        let size = self.layout.block * scale;
+        // Borders and insets keep their proportions to the block at every scale.
+        let inset = 4.0 * self.layout.scale() * scale;
+        let line = self.layout.scale() * scale;
 
         if phantom {
-            draw_rectangle_lines(
-                pos.x + 4.0,
-                pos.y + 4.0,
-                BLOCK_SIZE - 8.0,
-                BLOCK_SIZE - 8.0,
-                2.0,
-                color_var,
-            );
-        } else {
-            draw_rectangle(
-                pos.x + 1.0,
-                pos.y + 1.0,
-                BLOCK_SIZE - 2.0,
-                BLOCK_SIZE - 2.0,
-                Color::from_rgba(
-                    (color.0 as f32 / 1.2) as u8,
-                    (color.1 as f32 / 1.2) as u8,
-                    (color.2 as f32 / 1.2) as u8,
-                    150,
+            match self.theme.ghost {
+                GhostStyle::Outline => draw_rectangle_lines(
+                    pos.x + inset,
+                    pos.y + inset,
+                    size - inset * 2.0,
+                    size - inset * 2.0,
+                    2.0 * line,
+                    color_var,
                 ),
-            );
-            draw_rectangle_lines(
+                GhostStyle::Translucent => draw_rectangle(
+                    pos.x + line,
+                    pos.y + line,
+                    size - line * 2.0,
+                    size - line * 2.0,
+                    get_color(color, GHOST_ALPHA),
+                ),
+            }
+            return;
+        }
+
+        match (&self.theme.block, self.theme.tile(cell)) {
+            (BlockStyle::Atlas(atlas), Some(tile)) => draw_texture_ex(
+                atlas,
                 pos.x,
                 pos.y,
-                BLOCK_SIZE,
-                BLOCK_SIZE,
-                1.0,
-                get_color(UI_COLOR, 255),
-            );
-            draw_rectangle(
-                pos.x + 4.0,
-                pos.y + 4.0,
-                BLOCK_SIZE - 8.0,
-                BLOCK_SIZE - 8.0,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: Some(Vec2::splat(size)),
+                    source: Some(theme::atlas_source(tile, atlas.width())),
+                    ..Default::default()
+                },
+            ),
+            (BlockStyle::Classic, _) => {
+                draw_rectangle(
+                    pos.x + line,
+                    pos.y + line,
+                    size - line * 2.0,
+                    size - line * 2.0,
+                    Color::from_rgba(
+                        (color.0 as f32 / 1.2) as u8,
+                        (color.1 as f32 / 1.2) as u8,
+                        (color.2 as f32 / 1.2) as u8,
+                        150,
+                    ),
+                );
+                let outline = get_color(self.theme.background, 255);
+                draw_rectangle_lines(pos.x, pos.y, size, size, line, outline);
+                draw_rectangle(
+                    pos.x + inset,
+                    pos.y + inset,
+                    size - inset * 2.0,
+                    size - inset * 2.0,
+                    color_var,
+                );
+            }
+            // Empty cells of an atlas theme are drawn flat, the atlas only having blocks.
+            _ => draw_rectangle(
+                pos.x + line,
+                pos.y + line,
+                size - line * 2.0,
+                size - line * 2.0,
                 color_var,
-            );
+            ),
         }
     }
 }
@@ -0,0 +1,350 @@
+use crate::codec::{Reader, Writer};
+use crate::config::GameConfig;
+use crate::event::Event;
+use crate::game::Game;
+use crate::global::*;
+use crate::input::{Frame, Input};
+use crate::storage;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 4] = b"TREP";
+const VERSION: u16 = 4;
+const EXTENSION: &str = "replay";
+/// Name of the replay every game overwrites when it ends.
+pub const LAST_REPLAY: &str = "last";
+
+/// Playback speeds to pick from, in order.
+pub const SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+/// Most ticks a replay file may hold, about 46 hours at 60 frames per second.
+const MAX_STEPS: u64 = 10_000_000;
+/// Milliseconds of game time skipped by one seek.
+pub const SEEK_STEP: u64 = 5000;
+
+/// One tick of a recorded game: the input, and garbage received from an opponent after it.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Step {
+    pub frame: Frame,
+    pub garbage: u32,
+}
+
+/// Everything needed to play a game again exactly: its rules, its seed and every tick.
+///
+/// Files start with `TREP` and a `u16` version, followed by the seed, the rules and the
+/// ticks as runs of identical steps.
+pub struct Replay {
+    pub config: GameConfig,
+    pub seed: u64,
+    pub steps: Vec<Step>,
+}
+
+impl Replay {
+    pub fn new(config: GameConfig, seed: u64) -> Self {
+        Replay {
+            config,
+            seed,
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, frame: Frame) {
+        self.steps.push(Step { frame, garbage: 0 });
+    }
+
+    /// Notes garbage received after the most recent tick.
+    pub fn receive_garbage(&mut self, lines: u32) {
+        if let Some(step) = self.steps.last_mut() {
+            step.garbage += lines;
+        }
+    }
+
+    /// Length of the recorded game in milliseconds.
+    pub fn duration(&self) -> u64 {
+        self.steps.iter().map(|step| step.frame.delta).sum()
+    }
+
+    /// Path of the replay called `name` in the replay directory, created on first use.
+    pub fn path(name: &str) -> Option<PathBuf> {
+        let dir = storage::data_dir()?.join("replays");
+        fs::create_dir_all(&dir).ok()?;
+        return Some(dir.join(name).with_extension(EXTENSION));
+    }
+
+    /// Saves the replay as `name` in the replay directory.
+    pub fn save(&self, name: &str) {
+        if let Some(path) = Self::path(name) {
+            let _ = fs::write(path, self.encode());
+        }
+    }
+
+    /// Reads a replay file, returning `None` if it is unreadable, from another version or
+    /// holds rules or ticks out of range.
+    pub fn load(path: &Path) -> Option<Self> {
+        let bytes = fs::read(path).ok()?;
+        return Self::decode(&bytes);
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Writer::new(MAGIC, VERSION);
+        out.varint(self.seed);
+        self.config.encode(&mut out);
+
+        let mut runs: Vec<(Step, u64)> = Vec::new();
+        for &step in &self.steps {
+            match runs.last_mut() {
+                Some((last, count)) if *last == step => *count += 1,
+                _ => runs.push((step, 1)),
+            }
+        }
+        out.varint(runs.len() as u64);
+        for (step, count) in runs {
+            out.varint(count);
+            out.varint(step.frame.delta);
+            out.u8(step.frame.input.down);
+            out.u8(step.frame.input.pressed);
+            out.varint(step.garbage as u64);
+        }
+        return out.0;
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(bytes, MAGIC, VERSION)?;
+        let seed = reader.varint()?;
+        let config = GameConfig::decode(&mut reader)?;
+
+        let mut steps = Vec::new();
+        for _ in 0..reader.varint()? {
+            let count = reader.varint()?;
+            if steps.len() as u64 + count > MAX_STEPS {
+                return None;
+            }
+            let step = Step {
+                frame: Frame {
+                    delta: reader.varint()?,
+                    input: Input {
+                        down: reader.u8()?,
+                        pressed: reader.u8()?,
+                    },
+                },
+                garbage: u32::try_from(reader.varint()?).ok()?,
+            };
+            // Garbage arrives once per tick at most, from a single placement of the opponent.
+            if step.frame.delta > u32::MAX as u64 || step.garbage > MAX_ATTACK {
+                return None;
+            }
+            steps.extend((0..count).map(|_| step));
+        }
+
+        return Some(Replay {
+            config,
+            seed,
+            steps,
+        });
+    }
+}
+
+/// A replay being watched, fed through a fresh `Game` one recorded tick at a time.
+pub struct Playback {
+    replay: Replay,
+    pub game: Game,
+    position: usize,
+    /// Milliseconds of playback owed to the replay but not yet played.
+    pending: f64,
+    pub paused: bool,
+    pub speed: usize,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Playback {
+            game: Game::new(replay.config, replay.seed),
+            replay,
+            position: 0,
+            pending: 0.0,
+            paused: false,
+            speed: SPEEDS.iter().position(|&speed| speed == 1.0).unwrap_or(0),
+        }
+    }
+
+    pub fn duration(&self) -> u64 {
+        self.replay.duration()
+    }
+
+    /// Plays the ticks that fit in `delta` real milliseconds at the current speed, returning
+    /// the events they emitted.
+    pub fn update(&mut self, delta: u64) -> Vec<Event> {
+        let mut events = Vec::new();
+        if self.paused {
+            return events;
+        }
+
+        self.pending += delta as f64 * SPEEDS[self.speed];
+        while let Some(step) = self.replay.steps.get(self.position) {
+            if step.frame.delta as f64 > self.pending {
+                break;
+            }
+            self.pending -= step.frame.delta as f64;
+            events.extend_from_slice(self.advance());
+        }
+        return events;
+    }
+
+    /// Plays a single tick, returning the events it emitted.
+    pub fn step(&mut self) -> Vec<Event> {
+        return self.advance().to_vec();
+    }
+
+    /// Moves to the last tick at or before `time` milliseconds, replaying from the start
+    /// when seeking backwards.
+    pub fn seek(&mut self, time: u64) {
+        if time < self.game.time {
+            self.game = Game::new(self.replay.config, self.replay.seed);
+            self.position = 0;
+        }
+        while let Some(step) = self.replay.steps.get(self.position) {
+            if self.game.time + step.frame.delta > time {
+                break;
+            }
+            self.advance();
+        }
+        self.pending = 0.0;
+    }
+
+    fn advance(&mut self) -> &[Event] {
+        let Some(&step) = self.replay.steps.get(self.position) else {
+            return &[];
+        };
+        self.position += 1;
+        self.game.update(&step.frame.input, step.frame.delta);
+        self.game.receive_garbage(step.garbage);
+        return self.game.events();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Action;
+    use crate::mode::Mode;
+    use crate::speed::LevelRule;
+
+    fn recording(garbage: u32, delta: u64) -> Replay {
+        let mut replay = Replay::new(Mode::Versus.config(), 7);
+        for _ in 0..3 {
+            replay.record(Frame {
+                delta: 16,
+                input: Input::default(),
+            });
+        }
+        replay.record(Frame {
+            delta,
+            input: Input::default(),
+        });
+        replay.receive_garbage(garbage);
+        return replay;
+    }
+
+    #[test]
+    fn replays_round_trip() {
+        let replay = recording(MAX_ATTACK, 16);
+        let decoded = Replay::decode(&replay.encode()).unwrap();
+        assert_eq!(decoded.seed, replay.seed);
+        assert_eq!(decoded.steps, replay.steps);
+        assert_eq!(decoded.encode(), replay.encode());
+    }
+
+    #[test]
+    fn impossible_ticks_are_rejected() {
+        assert!(Replay::decode(&recording(MAX_ATTACK + 1, 16).encode()).is_none());
+        assert!(Replay::decode(&recording(u32::MAX, 16).encode()).is_none());
+        assert!(Replay::decode(&recording(0, u64::MAX).encode()).is_none());
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        let mut replay = recording(0, 16);
+        replay.config.level_rule = LevelRule::FixedLines(0);
+        assert!(Replay::decode(&replay.encode()).is_none());
+    }
+
+    #[test]
+    fn too_many_steps_are_rejected() {
+        let mut out = Writer::new(MAGIC, VERSION);
+        out.varint(7);
+        Mode::Marathon.config().encode(&mut out);
+        out.varint(1);
+        out.varint(MAX_STEPS + 1);
+        out.varint(16);
+        out.u8(0);
+        out.u8(0);
+        out.varint(0);
+        assert!(Replay::decode(&out.0).is_none());
+    }
+
+    #[test]
+    fn playback_repeats_the_game_exactly() {
+        let config = Mode::Versus.config();
+        let mut game = Game::new(config, 11);
+        let mut replay = Replay::new(config, 11);
+        let mut hashes = Vec::new();
+        // Pieces shifted, rotated, held and dropped all over the board at uneven frame times,
+        // with garbage now and then, until the stack tops out.
+        for tick in 0..10_000u64 {
+            let (piece, t) = (tick / 24, tick % 24);
+            let roll = crate::codec::fnv1a(&piece.to_le_bytes());
+            let side = if roll & 1 == 0 {
+                Action::Left
+            } else {
+                Action::Right
+            };
+            let spin = match roll >> 8 & 3 {
+                0 => Some(Action::RotateClockwise),
+                1 => Some(Action::RotateCounterclockwise),
+                _ => None,
+            };
+            let action = match t {
+                0..=11 if t % 2 == 0 && t / 2 < (roll >> 16) % 6 => Some(side),
+                13 => spin,
+                15 if (roll >> 24).is_multiple_of(6) => Some(Action::Hold),
+                22 => Some(Action::HardDrop),
+                _ => None,
+            };
+            let mut input = Input::default();
+            if let Some(action) = action {
+                input.set_down(action);
+                input.set_pressed(action);
+            }
+            let frame = Frame {
+                delta: 10 + tick % 13,
+                input,
+            };
+            game.update(&frame.input, frame.delta);
+            replay.record(frame);
+            if tick % 250 == 0 {
+                game.receive_garbage(2);
+                replay.receive_garbage(2);
+            }
+            hashes.push(game.state_hash());
+            if game.is_over() {
+                break;
+            }
+        }
+        assert!(game.is_over());
+        assert!(game.pieces > 10);
+
+        let mut playback = Playback::new(Replay::decode(&replay.encode()).unwrap());
+        for hash in &hashes {
+            playback.step();
+            assert_eq!(playback.game.state_hash(), *hash);
+        }
+        assert_eq!(playback.game.score, game.score);
+
+        // Seeking back starts over from the beginning and arrives at the same state.
+        let middle = playback.game.time / 2;
+        playback.seek(middle);
+        playback.seek(game.time);
+        assert_eq!(playback.game.state_hash(), game.state_hash());
+        playback.seek(middle);
+        assert!(hashes.contains(&playback.game.state_hash()));
+    }
+}
@@ -0,0 +1,69 @@
+use crate::codec::{Reader, Writer};
+use crate::game::Game;
+use crate::replay::Replay;
+use crate::storage;
+use std::fs;
+use std::path::PathBuf;
+
+const MAGIC: &[u8; 4] = b"TSAV";
+const VERSION: u16 = 3;
+const FILE_NAME: &str = "save.bin";
+
+/// What was found of a game left unfinished by the previous launch.
+pub enum Saved {
+    None,
+    /// The file was corrupted or written by another version, and has been moved aside.
+    Rejected,
+    /// The game and its recording so far, the replay covering the whole game once finished.
+    Game(Box<Game>, Box<Replay>),
+}
+
+fn path() -> Option<PathBuf> {
+    return storage::data_dir().map(|dir| dir.join(FILE_NAME));
+}
+
+/// Saves a game to resume on the next launch, replacing any earlier save.
+///
+/// The file starts with `TSAV` and a `u16` version, followed by the state of the game and
+/// the replay file recorded so far.
+pub fn save(game: &Game, recording: &Replay) {
+    let Some(path) = path() else {
+        return;
+    };
+
+    let mut out = Writer::new(MAGIC, VERSION);
+    game.save(&mut out);
+    out.0.extend_from_slice(&recording.encode());
+    let _ = fs::write(path, out.0);
+}
+
+/// Takes the saved game, if any, deleting the file so it is only resumed once.
+pub fn load() -> Saved {
+    let Some(path) = path() else {
+        return Saved::None;
+    };
+    let Ok(bytes) = fs::read(&path) else {
+        return Saved::None;
+    };
+
+    match decode(&bytes) {
+        Some((game, recording)) => {
+            let _ = fs::remove_file(&path);
+            return Saved::Game(Box::new(game), Box::new(recording));
+        }
+        None => {
+            storage::back_up(&path);
+            return Saved::Rejected;
+        }
+    }
+}
+
+fn decode(bytes: &[u8]) -> Option<(Game, Replay)> {
+    let mut reader = Reader::new(bytes, MAGIC, VERSION)?;
+    let game = Game::restore(&mut reader)?;
+    let recording = Replay::decode(reader.rest())?;
+    if recording.seed != game.seed() {
+        return None;
+    }
+    return Some((game, recording));
+}
@@ -0,0 +1,760 @@
+use crate::audio::Audio;
+use crate::config::{Delays, GameConfig};
+use crate::controls::{Controls, CONTROL_COUNT};
+use crate::event::Event;
+use crate::game::Game;
+use crate::gamepad::{Button, Gamepad, Gamepads};
+use crate::global::*;
+use crate::highscores::{Entry, HighScores};
+use crate::input::{Frame, Input, ACTIONS};
+use crate::menu::{self, *};
+use crate::mode::{Mode, MODES};
+use crate::net::{Network, Session};
+use crate::records::Records;
+use crate::renderer::Renderer;
+use crate::replay::{Playback, Replay, LAST_REPLAY, SEEK_STEP, SPEEDS};
+use crate::save;
+use crate::settings::Settings;
+use crate::theme::Themes;
+use macroquad::prelude::*;
+use std::path::PathBuf;
+
+/// A screen of the game, from the title to a match and the menus around it.
+pub enum Screen {
+    Title(usize),
+    ModeSelect(usize),
+    Settings(usize),
+    Connecting,
+    Replay,
+    Playing,
+    Paused(usize),
+    NameEntry(String),
+    Results,
+    HighScores {
+        mode: Mode,
+        highlight: Option<usize>,
+    },
+    Bindings {
+        /// The keyboard bindings of each player, then every connected controller.
+        device: usize,
+        selected: usize,
+        waiting: bool,
+    },
+}
+
+impl Screen {
+    /// Whether the screen belongs to a match, with the boards drawn underneath it.
+    fn is_match(&self) -> bool {
+        matches!(
+            self,
+            Screen::Connecting
+                | Screen::Replay
+                | Screen::Playing
+                | Screen::Paused(_)
+                | Screen::NameEntry(_)
+                | Screen::Results
+        )
+    }
+
+    /// Runs a frame of the screen, returning the changes of screen it asks for.
+    pub fn update(&mut self, ctx: &mut Context) -> Vec<Transition> {
+        match self {
+            Screen::Title(selected) => update_title(selected, ctx),
+            Screen::ModeSelect(selected) => update_mode_select(selected, ctx),
+            Screen::Settings(selected) => update_settings(selected, ctx),
+            Screen::Connecting => update_connecting(ctx),
+            Screen::Replay => update_replay(ctx),
+            Screen::Playing if ctx.session.is_some() => update_online(ctx),
+            Screen::Playing => update_playing(ctx),
+            Screen::Paused(selected) => update_paused(selected, ctx),
+            Screen::NameEntry(name) => update_name_entry(name, ctx),
+            Screen::Results => update_results(ctx),
+            Screen::Bindings {
+                device,
+                selected,
+                waiting,
+            } => update_bindings(device, selected, waiting, ctx),
+            Screen::HighScores { mode, highlight } => update_high_scores(mode, highlight, ctx),
+        }
+    }
+
+    /// Draws the screen, over the boards if it belongs to a match.
+    pub fn draw(&self, ctx: &Context) {
+        let renderer = &ctx.renderer;
+        let (settings, player) = (&ctx.settings, &ctx.player);
+        match self {
+            Screen::Title(selected) => {
+                let options = TITLE_OPTIONS.map(|option| option.label().to_owned());
+                renderer.draw_title(&options, *selected);
+            }
+            Screen::ModeSelect(selected) => {
+                let options = MODES.map(|mode| mode.title().to_owned());
+                let description = MODES[*selected].description();
+                renderer.draw_menu("Select Mode", &options, *selected, Some(description));
+            }
+            Screen::Settings(selected) => {
+                let options = SETTINGS_OPTIONS.map(|option| option.label(settings, renderer.theme));
+                renderer.draw_menu("Settings", &options, *selected, Some("←/→ - Change"));
+            }
+            Screen::Replay => match &ctx.playback {
+                Some(playback) => renderer.draw_replay_status(
+                    SPEEDS[playback.speed],
+                    playback.paused,
+                    playback.game.time,
+                    playback.duration(),
+                ),
+                None => {
+                    let path = ctx.replay.as_ref().map(|path| path.display().to_string());
+                    let lines = vec!["Could not read replay".to_owned(), path.unwrap_or_default()];
+                    renderer.draw_message(&lines);
+                }
+            },
+            Screen::Connecting => {
+                let lines = match (&ctx.connect_error, &ctx.session) {
+                    (Some(error), _) => vec!["Could not connect".to_owned(), error.clone()],
+                    (
+                        _,
+                        Some(Session {
+                            error: Some(error), ..
+                        }),
+                    ) => vec![error.message().to_owned()],
+                    _ => match &ctx.network {
+                        Some(Network::Host(port)) => {
+                            vec![format!("Waiting for an opponent on port {}", port)]
+                        }
+                        Some(Network::Connect(address)) => {
+                            vec![format!("Connecting to {}", address)]
+                        }
+                        None => Vec::new(),
+                    },
+                };
+                renderer.draw_message(&lines);
+            }
+            Screen::Playing => {
+                if let Some(latency) = ctx.session.as_ref().and_then(|session| session.latency) {
+                    renderer.draw_status(&format!("Ping: {} ms", latency));
+                }
+            }
+            Screen::Paused(selected) => {
+                let options: Vec<String> = PauseOption::all(ctx.rival.is_none())
+                    .iter()
+                    .map(|option| option.label().to_owned())
+                    .collect();
+                renderer.draw_pause(player, &settings.controls[0], &options, *selected);
+            }
+            Screen::Bindings {
+                device,
+                selected,
+                waiting,
+            } => {
+                let keyboards = settings.controls.len();
+                match device
+                    .checked_sub(keyboards)
+                    .and_then(|index| ctx.gamepads.pads().get(index))
+                {
+                    Some(pad) => renderer.draw_bindings(
+                        &pad.name,
+                        &settings.gamepad_controls(pad.id),
+                        *selected,
+                        *waiting,
+                    ),
+                    None => renderer.draw_bindings(
+                        &format!("Key Bindings - Player {}", device + 1),
+                        &settings.controls[*device],
+                        *selected,
+                        *waiting,
+                    ),
+                }
+            }
+            Screen::NameEntry(name) => renderer.draw_results(player, Some(name)),
+            Screen::Results => match &ctx.rival {
+                Some(rival) => {
+                    let error = ctx.session.as_ref().and_then(|session| session.error);
+                    renderer.draw_versus_results(
+                        [player, rival],
+                        error.map(|error| error.message()),
+                        ctx.session.is_none(),
+                    )
+                }
+                None => renderer.draw_results(player, None),
+            },
+            Screen::HighScores { mode, highlight } => {
+                renderer.draw_high_scores(*mode, &ctx.high_scores.top(*mode), *highlight)
+            }
+        }
+    }
+}
+
+/// A change of screen asked for by the screen on top.
+pub enum Transition {
+    /// Opens a screen over the current one, which it returns to once popped.
+    Push(Screen),
+    Pop,
+    Replace(Screen),
+    /// Closes every screen and starts over from the given one.
+    Reset(Screen),
+    Quit,
+}
+
+/// Screens stacked on top of each other, only the top one handling input.
+pub struct Screens(Vec<Screen>);
+
+impl Screens {
+    pub fn new(screens: Vec<Screen>) -> Self {
+        Screens(screens)
+    }
+
+    pub fn top(&self) -> &Screen {
+        self.0.last().expect("no screen")
+    }
+
+    pub fn top_mut(&mut self) -> &mut Screen {
+        self.0.last_mut().expect("no screen")
+    }
+
+    /// Whether a match is underway below the top screen, or on it.
+    pub fn in_match(&self) -> bool {
+        self.0.iter().any(Screen::is_match)
+    }
+
+    /// Whether a game is being played, possibly paused or with a menu open over it.
+    pub fn is_playing(&self) -> bool {
+        self.0
+            .iter()
+            .any(|screen| matches!(screen, Screen::Playing))
+    }
+
+    /// Applies `transitions` in order, returning `false` once the game should quit.
+    pub fn apply(&mut self, transitions: Vec<Transition>) -> bool {
+        for transition in transitions {
+            match transition {
+                Transition::Push(screen) => self.0.push(screen),
+                Transition::Pop => {
+                    self.0.pop();
+                }
+                Transition::Replace(screen) => {
+                    self.0.pop();
+                    self.0.push(screen);
+                }
+                Transition::Reset(screen) => self.0 = vec![screen],
+                Transition::Quit => return false,
+            }
+        }
+        // Leaving the bottom screen leaves the game.
+        return !self.0.is_empty();
+    }
+}
+
+/// What the screens work on: the match and its players, their settings and records, and
+/// what shows and plays it all.
+pub struct Context<'a> {
+    pub settings: Settings,
+    pub themes: &'a Themes,
+    pub renderer: Renderer<'a>,
+    /// Draws the second board of versus beside the first.
+    pub rival_renderer: Renderer<'a>,
+    pub audio: Audio,
+    pub gamepads: Gamepads,
+    pub high_scores: HighScores,
+    pub records: Records,
+    /// Rules given on the command line, which an online match is played by.
+    pub config: GameConfig,
+    pub player: Game,
+    /// The second board of a versus match, played here or over the network.
+    pub rival: Option<Game>,
+    pub recording: Replay,
+    /// Where to host or join an online match, if asked to.
+    pub network: Option<Network>,
+    pub session: Option<Session>,
+    /// Why the connection could not be set up, if it could not.
+    pub connect_error: Option<String>,
+    /// The replay file asked to be watched, if any.
+    pub replay: Option<PathBuf>,
+    pub playback: Option<Playback>,
+    /// The name last entered for a high score, offered again for the next one.
+    pub last_name: String,
+
+    /// Menu step taken this frame.
+    pub navigation: Option<Navigation>,
+    /// Milliseconds since the previous frame.
+    pub delta: u64,
+    /// Whether a controller was pulled out this frame.
+    pub pad_lost: bool,
+}
+
+impl Context<'_> {
+    /// People playing on this machine.
+    fn players(&self) -> usize {
+        if self.rival.is_some() && self.session.is_none() {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Replaces the match with a new local one.
+    fn start_match(&mut self, config: GameConfig, seed: u64) {
+        (self.player, self.rival, self.recording) = new_match(config, seed);
+    }
+
+    /// What `player` holds and presses on the keyboard and their controllers.
+    fn read_input(&self, player: usize) -> Input {
+        let mut input = Input::default();
+        let pads: Vec<(&Gamepad, Controls<Button>)> = self
+            .gamepads
+            .of_player(player, self.players())
+            .map(|pad| (pad, self.settings.gamepad_controls(pad.id)))
+            .collect();
+
+        for action in ACTIONS {
+            for &key in self.settings.controls[player].keys(action) {
+                if is_key_down(key) {
+                    input.set_down(action);
+                }
+                if is_key_pressed(key) {
+                    input.set_pressed(action);
+                }
+            }
+            for (pad, controls) in &pads {
+                for &button in controls.keys(action) {
+                    if pad.is_down(button) {
+                        input.set_down(action);
+                    }
+                    if pad.is_pressed(button) {
+                        input.set_pressed(action);
+                    }
+                }
+            }
+        }
+        return input;
+    }
+
+    /// Whether any of the players on this machine pressed pause.
+    fn is_pause_pressed(&self) -> bool {
+        let players = self.players();
+        (0..players).any(|player| {
+            let keys = &self.settings.controls[player].pause;
+            let pads = self.gamepads.of_player(player, players);
+            keys.iter().any(|&key| is_key_pressed(key))
+                || pads.into_iter().any(|pad| {
+                    let buttons = self.settings.gamepad_controls(pad.id).pause;
+                    buttons.iter().any(|&button| pad.is_pressed(button))
+                })
+        })
+    }
+}
+
+/// Starts a local match, adding a second board dealt the same pieces in versus.
+pub fn new_match(config: GameConfig, seed: u64) -> (Game, Option<Game>, Replay) {
+    let versus = config.mode.is_versus();
+    let rival = versus.then(|| Game::new(config, seed));
+    return (Game::new(config, seed), rival, Replay::new(config, seed));
+}
+
+/// Passes the garbage each player sent this tick on to the other, returning what `first`
+/// received.
+fn exchange_garbage(first: &mut Game, second: &mut Game) -> u32 {
+    let sent = |game: &Game| -> u32 {
+        game.events()
+            .iter()
+            .map(|event| match event {
+                Event::Attack(lines) => *lines,
+                _ => 0,
+            })
+            .sum()
+    };
+    let (to_second, to_first) = (sent(first), sent(second));
+    first.receive_garbage(to_first);
+    second.receive_garbage(to_second);
+    return to_first;
+}
+
+/// The key bindings menu, opened on the first player's keyboard.
+fn bindings() -> Screen {
+    Screen::Bindings {
+        device: 0,
+        selected: 0,
+        waiting: false,
+    }
+}
+
+fn update_title(selected: &mut usize, ctx: &mut Context) -> Vec<Transition> {
+    let navigation = ctx.navigation;
+    menu::select(selected, TITLE_OPTIONS.len(), navigation);
+    match (navigation, TITLE_OPTIONS[*selected]) {
+        (Some(Navigation::Confirm), TitleOption::Play) => {
+            let current = MODES
+                .iter()
+                .position(|&mode| mode == ctx.player.config.mode);
+            vec![Transition::Push(Screen::ModeSelect(current.unwrap_or(0)))]
+        }
+        (Some(Navigation::Confirm), TitleOption::HighScores) => {
+            vec![Transition::Push(Screen::HighScores {
+                mode: Mode::Marathon,
+                highlight: None,
+            })]
+        }
+        (Some(Navigation::Confirm), TitleOption::Settings) => {
+            vec![Transition::Push(Screen::Settings(0))]
+        }
+        (Some(Navigation::Confirm), TitleOption::Quit) => vec![Transition::Quit],
+        (Some(Navigation::Back), _) => {
+            *selected = TITLE_OPTIONS.len() - 1;
+            Vec::new()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn update_mode_select(selected: &mut usize, ctx: &mut Context) -> Vec<Transition> {
+    menu::select(selected, MODES.len(), ctx.navigation);
+    match ctx.navigation {
+        Some(Navigation::Confirm) => {
+            let config = GameConfig {
+                handling: ctx.settings.handling,
+                delays: ctx.settings.delays(),
+                ..MODES[*selected].config()
+            };
+            ctx.start_match(config, time_seed());
+            ctx.renderer.personal_best = ctx.records.get(&ctx.player).cloned();
+            vec![Transition::Replace(Screen::Playing)]
+        }
+        Some(Navigation::Back) => vec![Transition::Pop],
+        _ => Vec::new(),
+    }
+}
+
+fn update_settings(selected: &mut usize, ctx: &mut Context) -> Vec<Transition> {
+    let navigation = ctx.navigation;
+    let settings = &mut ctx.settings;
+    menu::select(selected, SETTINGS_OPTIONS.len(), navigation);
+    let option = SETTINGS_OPTIONS[*selected];
+    match navigation {
+        Some(Navigation::Left) => option.adjust(settings, false),
+        Some(Navigation::Right) => option.adjust(settings, true),
+        _ => {}
+    }
+    // The theme shows straight away, letting players look through them.
+    if let (SettingsOption::Theme, Some(Navigation::Left | Navigation::Right)) =
+        (option, navigation)
+    {
+        let forward = navigation == Some(Navigation::Right);
+        settings.theme = ctx.themes.cycle(&settings.theme, forward);
+    }
+
+    let confirm = navigation == Some(Navigation::Confirm);
+    if confirm && option == SettingsOption::Fullscreen {
+        settings.fullscreen = !settings.fullscreen;
+    }
+    if confirm && option == SettingsOption::ClearDelay {
+        settings.clear_delay = !settings.clear_delay;
+    }
+    if confirm && option == SettingsOption::KeyBindings {
+        vec![Transition::Push(bindings())]
+    } else if navigation == Some(Navigation::Back) || (confirm && option == SettingsOption::Back) {
+        settings.save();
+        vec![Transition::Pop]
+    } else {
+        Vec::new()
+    }
+}
+
+fn update_connecting(ctx: &mut Context) -> Vec<Transition> {
+    let mut transitions = Vec::new();
+    if let Some(session) = &mut ctx.session {
+        session.poll();
+        if let (true, Some(peer)) = (session.is_ready(), session.peer) {
+            // Both sides simulate both boards, but only handling is exchanged, so online
+            // matches are played without delays.
+            ctx.config.delays = Delays::default();
+            let remote = GameConfig {
+                handling: peer.handling,
+                ..ctx.config
+            };
+            ctx.player = Game::new(ctx.config, session.seed());
+            ctx.recording = Replay::new(ctx.config, session.seed());
+            ctx.rival = Some(Game::new(remote, session.seed()));
+            transitions.push(Transition::Replace(Screen::Playing));
+        }
+    }
+    if ctx.navigation == Some(Navigation::Back) {
+        transitions.push(Transition::Quit);
+    }
+    transitions
+}
+
+fn update_replay(ctx: &mut Context) -> Vec<Transition> {
+    if let Some(playback) = &mut ctx.playback {
+        if is_key_pressed(KeyCode::Space) {
+            playback.paused = !playback.paused;
+        } else if is_key_pressed(KeyCode::Up) {
+            playback.speed = (playback.speed + 1).min(SPEEDS.len() - 1);
+        } else if is_key_pressed(KeyCode::Down) {
+            playback.speed = playback.speed.saturating_sub(1);
+        } else if is_key_pressed(KeyCode::Right) {
+            playback.seek(playback.game.time + SEEK_STEP);
+        } else if is_key_pressed(KeyCode::Left) {
+            playback.seek(playback.game.time.saturating_sub(SEEK_STEP));
+        } else if is_key_pressed(KeyCode::Home) {
+            playback.seek(0);
+        } else if is_key_pressed(KeyCode::Period) && playback.paused {
+            let events = playback.step();
+            ctx.renderer.handle_events(&events);
+            ctx.audio.handle_events(&events);
+        }
+        let events = playback.update(ctx.delta);
+        ctx.renderer.handle_events(&events);
+        ctx.audio.handle_events(&events);
+    }
+    if ctx.navigation == Some(Navigation::Back) {
+        vec![Transition::Quit]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Online matches cannot pause, as the other side would stall waiting for input.
+fn update_online(ctx: &mut Context) -> Vec<Transition> {
+    let input = ctx.read_input(0);
+    let (Some(session), Some(rival)) = (&mut ctx.session, &mut ctx.rival) else {
+        return vec![Transition::Quit];
+    };
+    session.send_frame(Frame {
+        delta: ctx.delta,
+        input,
+    });
+    session.poll();
+
+    let player = &mut ctx.player;
+    while let Some((local, remote)) = session.next_tick() {
+        player.update(&local.input, local.delta);
+        ctx.recording.record(local);
+        ctx.renderer.handle_events(player.events());
+        ctx.audio.handle_events(player.events());
+        rival.update(&remote.input, remote.delta);
+        ctx.rival_renderer.handle_events(rival.events());
+        ctx.recording
+            .receive_garbage(exchange_garbage(player, rival));
+        session.end_tick(player.state_hash().wrapping_add(rival.state_hash()));
+
+        if player.is_over() || rival.is_over() {
+            break;
+        }
+    }
+
+    if session.error.is_some() || player.is_over() || rival.is_over() {
+        ctx.recording.save(LAST_REPLAY);
+        vec![Transition::Replace(Screen::Results)]
+    } else {
+        Vec::new()
+    }
+}
+
+fn update_playing(ctx: &mut Context) -> Vec<Transition> {
+    // Losing a controller mid-game pauses rather than leaving a piece to fall.
+    if ctx.pad_lost || ctx.is_pause_pressed() {
+        return vec![Transition::Push(Screen::Paused(0))];
+    }
+
+    let frame = Frame {
+        delta: ctx.delta,
+        input: ctx.read_input(0),
+    };
+    ctx.player.update(&frame.input, frame.delta);
+    ctx.recording.record(frame);
+    ctx.renderer.handle_events(ctx.player.events());
+    ctx.audio.handle_events(ctx.player.events());
+
+    let input = ctx.rival.is_some().then(|| ctx.read_input(1));
+    if let (Some(rival), Some(input)) = (&mut ctx.rival, input) {
+        rival.update(&input, ctx.delta);
+        ctx.rival_renderer.handle_events(rival.events());
+        ctx.audio.handle_events(rival.events());
+        ctx.recording
+            .receive_garbage(exchange_garbage(&mut ctx.player, rival));
+    }
+
+    let player = &ctx.player;
+    if let Some(rival) = &ctx.rival {
+        if player.is_over() || rival.is_over() {
+            ctx.recording.save(LAST_REPLAY);
+            vec![Transition::Replace(Screen::Results)]
+        } else {
+            Vec::new()
+        }
+    } else if player.is_over() {
+        ctx.recording.save(LAST_REPLAY);
+        if ctx.records.submit(player) {
+            ctx.records.save();
+            if let Some(key) = Records::key(player) {
+                ctx.recording.save(&key);
+            }
+        }
+        ctx.renderer.personal_best = ctx.records.get(player).cloned();
+
+        let entry = Entry::from_game(player, "");
+        let mut transitions = vec![Transition::Replace(Screen::Results)];
+        if player.is_ranked() && ctx.high_scores.qualifies(&entry) {
+            transitions.push(Transition::Push(Screen::NameEntry(ctx.last_name.clone())));
+        }
+        transitions
+    } else {
+        Vec::new()
+    }
+}
+
+fn update_paused(selected: &mut usize, ctx: &mut Context) -> Vec<Transition> {
+    let options = PauseOption::all(ctx.rival.is_none());
+    menu::select(selected, options.len(), ctx.navigation);
+    let resume = ctx.is_pause_pressed();
+    if resume || ctx.navigation == Some(Navigation::Back) {
+        vec![Transition::Pop]
+    } else if ctx.navigation == Some(Navigation::Confirm) {
+        match options[*selected] {
+            PauseOption::Resume => vec![Transition::Pop],
+            PauseOption::Restart => {
+                ctx.start_match(ctx.player.config, time_seed());
+                vec![Transition::Pop]
+            }
+            PauseOption::KeyBindings => vec![Transition::Push(bindings())],
+            PauseOption::SaveAndQuit => {
+                save::save(&ctx.player, &ctx.recording);
+                vec![Transition::Quit]
+            }
+            PauseOption::QuitToTitle => vec![Transition::Reset(Screen::Title(0))],
+        }
+    } else {
+        Vec::new()
+    }
+}
+
+fn update_name_entry(name: &mut String, ctx: &mut Context) -> Vec<Transition> {
+    while let Some(c) = get_char_pressed() {
+        if (c.is_alphanumeric() || " -_.".contains(c)) && name.len() < NAME_LENGTH {
+            name.push(c);
+        }
+    }
+    if is_key_pressed(KeyCode::Backspace) {
+        name.pop();
+    }
+    if is_key_pressed(KeyCode::Enter) || ctx.gamepads.any_pressed(Button::Start) {
+        let name = name.trim().to_owned();
+        let rank = ctx.high_scores.insert(Entry::from_game(&ctx.player, &name));
+        ctx.high_scores.save();
+        ctx.last_name = name;
+        vec![Transition::Replace(Screen::HighScores {
+            mode: ctx.player.config.mode,
+            highlight: rank,
+        })]
+    } else {
+        Vec::new()
+    }
+}
+
+fn update_results(ctx: &mut Context) -> Vec<Transition> {
+    let gamepads = &ctx.gamepads;
+    // Hard drop and rotation keys might still be mashed as the game ends, so only Enter and
+    // Start retry.
+    let retry = is_key_pressed(KeyCode::Enter) || gamepads.any_pressed(Button::Start);
+    let scores = is_key_pressed(KeyCode::H) || gamepads.any_pressed(Button::North);
+    let leave = is_key_pressed(KeyCode::Escape) || gamepads.any_pressed(Button::Select);
+    if retry && ctx.session.is_none() {
+        ctx.start_match(ctx.player.config, time_seed());
+        vec![Transition::Replace(Screen::Playing)]
+    } else if scores && ctx.rival.is_none() {
+        vec![Transition::Push(Screen::HighScores {
+            mode: ctx.player.config.mode,
+            highlight: None,
+        })]
+    } else if leave && ctx.session.is_some() {
+        vec![Transition::Quit]
+    } else if leave {
+        vec![Transition::Reset(Screen::Title(0))]
+    } else {
+        Vec::new()
+    }
+}
+
+fn update_bindings(
+    device: &mut usize,
+    selected: &mut usize,
+    waiting: &mut bool,
+    ctx: &mut Context,
+) -> Vec<Transition> {
+    let (settings, gamepads) = (&mut ctx.settings, &ctx.gamepads);
+    let keyboards = settings.controls.len();
+    if *device >= keyboards + gamepads.pads().len() {
+        *device = 0;
+    }
+    let pad = device
+        .checked_sub(keyboards)
+        .and_then(|index| gamepads.pads().get(index));
+
+    let mut transitions = Vec::new();
+    if *waiting {
+        let bound = match pad {
+            Some(pad) => pad.last_pressed().map(|button| {
+                settings
+                    .gamepad_controls_mut(pad.id)
+                    .bind(*selected, button)
+            }),
+            None => {
+                get_last_key_pressed().map(|key| settings.controls[*device].bind(*selected, key))
+            }
+        };
+        // A controller has no key to spare for cancelling, the keyboard does.
+        if bound.is_some() || (pad.is_some() && is_key_pressed(KeyCode::Escape)) {
+            *waiting = false;
+        }
+    } else if is_key_pressed(KeyCode::Tab) || gamepads.any_pressed(Button::RightShoulder) {
+        *device = (*device + 1) % (keyboards + gamepads.pads().len());
+    } else if ctx.navigation == Some(Navigation::Confirm) {
+        *waiting = true;
+    } else if is_key_pressed(KeyCode::Backspace)
+        || is_key_pressed(KeyCode::Delete)
+        || gamepads.any_pressed(Button::West)
+    {
+        match pad {
+            Some(pad) => settings
+                .gamepad_controls_mut(pad.id)
+                .entry_mut(*selected)
+                .clear(),
+            None => settings.controls[*device].entry_mut(*selected).clear(),
+        }
+    } else if is_key_pressed(KeyCode::R) {
+        match pad {
+            Some(pad) => *settings.gamepad_controls_mut(pad.id) = Controls::gamepad(),
+            None => settings.controls[*device] = Controls::defaults(*device),
+        }
+    } else if ctx.navigation == Some(Navigation::Back) {
+        settings.save();
+        transitions.push(Transition::Pop);
+    } else {
+        menu::select(selected, CONTROL_COUNT, ctx.navigation);
+    }
+    transitions
+}
+
+fn update_high_scores(
+    mode: &mut Mode,
+    highlight: &mut Option<usize>,
+    ctx: &mut Context,
+) -> Vec<Transition> {
+    // Versus keeps no high scores.
+    let ranked: Vec<Mode> = MODES.into_iter().filter(|mode| !mode.is_versus()).collect();
+    let index = ranked.iter().position(|ranked| ranked == mode).unwrap_or(0);
+    match ctx.navigation {
+        Some(Navigation::Left) => {
+            *mode = ranked[(index + ranked.len() - 1) % ranked.len()];
+            *highlight = None;
+            Vec::new()
+        }
+        Some(Navigation::Right) => {
+            *mode = ranked[(index + 1) % ranked.len()];
+            *highlight = None;
+            Vec::new()
+        }
+        Some(Navigation::Confirm) | Some(Navigation::Back) => vec![Transition::Pop],
+        _ => Vec::new(),
+    }
+}
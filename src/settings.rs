@@ -0,0 +1,204 @@
+use crate::config::{Delays, Handling};
+use crate::controls::*;
+use crate::gamepad::{Button, GamepadId};
+use crate::global::*;
+use crate::storage;
+use std::fs;
+use std::path::PathBuf;
+
+const HEADER: &str = "tetris-settings";
+const VERSION: u32 = 1;
+const FILE_NAME: &str = "settings.txt";
+/// Prefix of the second player's control names.
+const PLAYER2_PREFIX: &str = "p2_";
+/// Prefix of controller bindings, followed by the controller id as in `pad_045e_028e_`.
+const GAMEPAD_PREFIX: &str = "pad_";
+
+/// Player preferences kept in the config directory as `name = value` lines.
+pub struct Settings {
+    path: Option<PathBuf>,
+    /// Bindings of both players, the second only used in versus.
+    pub controls: [Controls; 2],
+    /// Bindings of every controller model used so far.
+    pub gamepads: Vec<(GamepadId, Controls<Button>)>,
+    pub handling: Handling,
+    pub fullscreen: bool,
+    /// Whether the game waits for the line clear effects to play, as in `Delays::ANIMATED`.
+    pub clear_delay: bool,
+    /// Id of the theme, falling back to the classic one if it is gone.
+    pub theme: String,
+    /// Volume of the sound effects as a percentage.
+    pub sfx_volume: u32,
+    /// Volume of the music as a percentage.
+    pub music_volume: u32,
+    pub muted: bool,
+}
+
+impl Settings {
+    /// Loads the settings, keeping the default for anything missing or unreadable.
+    pub fn load() -> Self {
+        let path = storage::config_dir().map(|dir| dir.join(FILE_NAME));
+        let mut settings = Settings {
+            path,
+            controls: [Controls::player1(), Controls::player2()],
+            gamepads: Vec::new(),
+            handling: Handling::default(),
+            fullscreen: false,
+            clear_delay: false,
+            theme: "classic".to_owned(),
+            sfx_volume: 80,
+            music_volume: 50,
+            muted: false,
+        };
+
+        let Some(path) = &settings.path else {
+            return settings;
+        };
+        let Ok(contents) = fs::read_to_string(path) else {
+            settings.save();
+            return settings;
+        };
+
+        let mut lines = contents.lines();
+        if lines.next() != Some(&format!("{} {}", HEADER, VERSION)) {
+            storage::back_up(path);
+            return settings;
+        }
+
+        for line in lines {
+            if let Some((name, value)) = line.split_once('=') {
+                settings.apply(name.trim(), value.trim());
+            }
+        }
+        return settings;
+    }
+
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let mut contents = format!("{} {}\n", HEADER, VERSION);
+        for (player, controls) in self.controls.iter().enumerate() {
+            let prefix = if player == 0 { "" } else { PLAYER2_PREFIX };
+            write_controls(&mut contents, prefix, controls);
+        }
+        for (id, controls) in &self.gamepads {
+            write_controls(
+                &mut contents,
+                &format!("{}{}_", GAMEPAD_PREFIX, id),
+                controls,
+            );
+        }
+        let sdf = if self.handling.sdf >= INFINITE_SDF {
+            "inf".to_owned()
+        } else {
+            self.handling.sdf.to_string()
+        };
+        contents += &format!("das = {}\n", self.handling.das);
+        contents += &format!("arr = {}\n", self.handling.arr);
+        contents += &format!("sdf = {}\n", sdf);
+        contents += &format!("fullscreen = {}\n", self.fullscreen);
+        contents += &format!("clear_delay = {}\n", self.clear_delay);
+        contents += &format!("theme = {}\n", self.theme);
+        contents += &format!("sfx_volume = {}\n", self.sfx_volume);
+        contents += &format!("music_volume = {}\n", self.music_volume);
+        contents += &format!("muted = {}\n", self.muted);
+        let _ = fs::write(path, contents);
+    }
+
+    fn apply(&mut self, name: &str, value: &str) {
+        match name {
+            "das" => {
+                let das: u64 = value.parse().unwrap_or(self.handling.das);
+                self.handling.das = das.min(MAX_DAS);
+            }
+            "arr" => {
+                let arr: u64 = value.parse().unwrap_or(self.handling.arr);
+                self.handling.arr = arr.min(MAX_ARR);
+            }
+            "sdf" if value == "inf" => self.handling.sdf = INFINITE_SDF,
+            "sdf" => {
+                let sdf: u32 = value.parse().unwrap_or(self.handling.sdf);
+                self.handling.sdf = sdf.clamp(1, INFINITE_SDF);
+            }
+            "fullscreen" => self.fullscreen = value.parse().unwrap_or(self.fullscreen),
+            "clear_delay" => self.clear_delay = value.parse().unwrap_or(self.clear_delay),
+            "theme" => self.theme = value.to_owned(),
+            "sfx_volume" => {
+                let volume: u32 = value.parse().unwrap_or(self.sfx_volume);
+                self.sfx_volume = volume.min(100);
+            }
+            "music_volume" => {
+                let volume: u32 = value.parse().unwrap_or(self.music_volume);
+                self.music_volume = volume.min(100);
+            }
+            "muted" => self.muted = value.parse().unwrap_or(self.muted),
+            _ => {}
+        }
+
+        if let Some(rest) = name.strip_prefix(GAMEPAD_PREFIX) {
+            // The id itself contains an underscore, as in `045e_028e`.
+            let Some(id) = rest.get(..9).and_then(GamepadId::parse) else {
+                return;
+            };
+            let name = rest[9..].trim_start_matches('_');
+            apply_controls(self.gamepad_controls_mut(id), name, value);
+            return;
+        }
+
+        let (player, name) = match name.strip_prefix(PLAYER2_PREFIX) {
+            Some(name) => (1, name),
+            None => (0, name),
+        };
+        apply_controls(&mut self.controls[player], name, value);
+    }
+
+    /// The delays new games are played with.
+    pub fn delays(&self) -> Delays {
+        if self.clear_delay {
+            Delays::ANIMATED
+        } else {
+            Delays::default()
+        }
+    }
+
+    /// The bindings of controller model `id`, the defaults until changed.
+    pub fn gamepad_controls(&self, id: GamepadId) -> Controls<Button> {
+        self.gamepads
+            .iter()
+            .find(|(known, _)| *known == id)
+            .map_or_else(Controls::gamepad, |(_, controls)| controls.clone())
+    }
+
+    /// The bindings of controller model `id`, added with the defaults if it has none yet.
+    pub fn gamepad_controls_mut(&mut self, id: GamepadId) -> &mut Controls<Button> {
+        let index = match self.gamepads.iter().position(|(known, _)| *known == id) {
+            Some(index) => index,
+            None => {
+                self.gamepads.push((id, Controls::gamepad()));
+                self.gamepads.len() - 1
+            }
+        };
+        return &mut self.gamepads[index].1;
+    }
+}
+
+fn write_controls<B: Binding>(contents: &mut String, prefix: &str, controls: &Controls<B>) {
+    for i in 0..CONTROL_COUNT {
+        let (name, _, keys) = controls.entry(i);
+        let keys: Vec<&str> = keys.iter().filter_map(|&key| key.name()).collect();
+        *contents += &format!("{}{} = {}\n", prefix, name, keys.join(", "));
+    }
+}
+
+fn apply_controls<B: Binding>(controls: &mut Controls<B>, name: &str, value: &str) {
+    for i in 0..CONTROL_COUNT {
+        if controls.entry(i).0 == name {
+            *controls.entry_mut(i) = value
+                .split(',')
+                .filter_map(|key| B::from_name(key.trim()))
+                .collect();
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Data-driven gravity curves and level progression rules.
+//!
+//! Gravity is measured in 1/65536ths of a row per 1/60 s frame, so `GRAVITY_1G` falls one
+//! row every frame and `GRAVITY_20G` drops pieces straight to the floor.
+
+pub const GRAVITY_1G: u64 = 65536;
+pub const GRAVITY_20G: u64 = 20 * GRAVITY_1G;
+
+/// NES frames per row for levels 0 to 29, the last entry covering all higher levels.
+const NES_FRAMES: [u64; 30] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+    1,
+];
+
+/// TGM internal gravity in 1/256 G, applying from the given level on.
+const TGM_GRAVITY: [(u32, u64); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, 5120),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GravityCurve {
+    /// `(0.8 - (level - 1) * 0.007) ^ (level - 1)` seconds per row, counting levels from 1.
+    Guideline,
+    /// Frame counts of the NES version.
+    Nes,
+    /// Sub-row gravity of the arcade TGM series, reaching 20G at level 500.
+    Tgm,
+}
+
+impl GravityCurve {
+    pub fn gravity(self, level: u32) -> u64 {
+        match self {
+            GravityCurve::Guideline => {
+                let level = level.min(20) as f64;
+                let seconds = (0.8 - level * 0.007).powf(level);
+                ((GRAVITY_1G as f64 / (seconds * 60.0)) as u64).min(GRAVITY_20G)
+            }
+            GravityCurve::Nes => {
+                GRAVITY_1G / NES_FRAMES[(level as usize).min(NES_FRAMES.len() - 1)]
+            }
+            GravityCurve::Tgm => step(&TGM_GRAVITY, level) * (GRAVITY_1G / 256),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GravityCurve::Guideline => "guideline",
+            GravityCurve::Nes => "nes",
+            GravityCurve::Tgm => "tgm",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "guideline" => Some(GravityCurve::Guideline),
+            "nes" => Some(GravityCurve::Nes),
+            "tgm" => Some(GravityCurve::Tgm),
+            _ => None,
+        }
+    }
+}
+
+/// How the level goes up as the game progresses.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LevelRule {
+    /// The level never changes.
+    Fixed,
+    /// One level per the given number of lines.
+    FixedLines(u32),
+    /// Each level takes five lines more than the one before it.
+    VariableGoal,
+    /// One level per piece and per cleared line, pieces alone stopping at every x99.
+    PerPiece,
+}
+
+impl LevelRule {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fixed" => Some(LevelRule::FixedLines(10)),
+            "variable" => Some(LevelRule::VariableGoal),
+            "piece" => Some(LevelRule::PerPiece),
+            _ => None,
+        }
+    }
+
+    /// Lines needed to leave `level`, for rules that count lines.
+    pub fn goal(self, level: u32) -> Option<u32> {
+        match self {
+            LevelRule::Fixed => None,
+            LevelRule::FixedLines(lines) => Some(lines),
+            LevelRule::VariableGoal => Some(5 * (level + 1)),
+            LevelRule::PerPiece => None,
+        }
+    }
+}
+
+/// Looks up the value of a table of `(from_level, value)` steps sorted by level.
+pub fn step(table: &[(u32, u64)], level: u32) -> u64 {
+    table
+        .iter()
+        .take_while(|(from, _)| *from <= level)
+        .last()
+        .map_or(table[0].1, |(_, value)| *value)
+}
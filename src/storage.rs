@@ -0,0 +1,37 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Directory for files that persist between launches, created on first use.
+///
+/// Follows `$XDG_DATA_HOME` on Unix and `%APPDATA%` on Windows.
+pub fn data_dir() -> Option<PathBuf> {
+    return app_dir("XDG_DATA_HOME", ".local/share");
+}
+
+/// Directory for user settings, following `$XDG_CONFIG_HOME` on Unix and `%APPDATA%` on Windows.
+pub fn config_dir() -> Option<PathBuf> {
+    return app_dir("XDG_CONFIG_HOME", ".config");
+}
+
+fn app_dir(xdg_var: &str, home_fallback: &str) -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os(xdg_var)
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(home_fallback)))
+    };
+
+    let dir = base?.join("tetris");
+    fs::create_dir_all(&dir).ok()?;
+    return Some(dir);
+}
+
+/// Moves an unreadable file aside so saving over it does not lose it for good.
+pub fn back_up(path: &PathBuf) {
+    let mut backup = path.clone().into_os_string();
+    backup.push(".bak");
+    let _ = fs::rename(path, backup);
+}
@@ -0,0 +1,257 @@
+use crate::assets::Asset;
+use crate::global::*;
+use crate::storage;
+use macroquad::prelude::*;
+use std::fs;
+use std::path::Path;
+
+const HEADER: &str = "tetris-theme";
+const VERSION: u32 = 1;
+/// Themes shipped with the game, in the order the settings cycle through them.
+const BUILT_IN: [&str; 4] = ["classic", "flat", "high-contrast", "colorblind"];
+/// Names of the piece colors in a theme file, in the order of `SHAPES`.
+const PIECE_NAMES: [&str; 7] = ["i", "j", "l", "o", "s", "t", "z"];
+/// Tiles of a block atlas, the pieces in the order of `SHAPES` and then garbage.
+const ATLAS_TILES: usize = 8;
+
+/// How a block is drawn.
+pub enum BlockStyle {
+    /// A bright square inside a darker, outlined one.
+    Classic,
+    /// A plain square.
+    Flat,
+    /// Square tiles cut from a texture, one per piece and one for garbage side by side.
+    Atlas(Texture2D),
+}
+
+/// How the landing spot of the falling piece is shown.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GhostStyle {
+    Outline,
+    Translucent,
+}
+
+/// The look of the game, read from a file of `name = value` lines.
+///
+/// The board keeps the classic colors of the pieces whatever the theme, so themes map
+/// them to their own colors only when drawing.
+pub struct Theme {
+    /// Name of the file without its extension, as saved in the settings.
+    pub id: String,
+    pub name: String,
+    pub pieces: [(u8, u8, u8); 7],
+    pub garbage: (u8, u8, u8),
+    pub board: (u8, u8, u8),
+    pub background: (u8, u8, u8),
+    pub text: (u8, u8, u8),
+    pub warning: (u8, u8, u8),
+    pub selected: (u8, u8, u8),
+    pub block: BlockStyle,
+    pub ghost: GhostStyle,
+    /// Drawn stretched over the background color.
+    pub background_image: Option<Texture2D>,
+    /// Replaces the font of the game.
+    pub font: Option<Font>,
+}
+
+impl Theme {
+    fn classic(id: &str) -> Self {
+        Theme {
+            id: id.to_owned(),
+            name: id.to_owned(),
+            pieces: SHAPES.map(|shape| shape.color),
+            garbage: GARBAGE_COLOR,
+            board: BOARD_COLOR,
+            background: (30, 30, 30),
+            text: (255, 255, 255),
+            warning: (230, 60, 60),
+            selected: (250, 200, 60),
+            block: BlockStyle::Classic,
+            ghost: GhostStyle::Outline,
+            background_image: None,
+            font: None,
+        }
+    }
+
+    /// Reads a theme, with `load` fetching the images and fonts it names.
+    ///
+    /// Anything missing or unreadable keeps its classic look, but a file without the
+    /// header is not a theme at all.
+    fn parse(id: &str, contents: &str, load: impl Fn(&str) -> Option<Vec<u8>>) -> Option<Self> {
+        let mut lines = contents.lines();
+        if lines.next().map(str::trim) != Some(&format!("{} {}", HEADER, VERSION)) {
+            return None;
+        }
+
+        let mut theme = Self::classic(id);
+        let mut atlas = None;
+        let mut block = "classic";
+        for line in lines {
+            let Some((name, value)) = line.split_once('=') else {
+                continue;
+            };
+            let (name, value) = (name.trim(), value.trim());
+            if let Some(index) = PIECE_NAMES.iter().position(|&piece| piece == name) {
+                theme.pieces[index] = parse_color(value).unwrap_or(theme.pieces[index]);
+                continue;
+            }
+
+            let color = match name {
+                "garbage" => &mut theme.garbage,
+                "board" => &mut theme.board,
+                "background" => &mut theme.background,
+                "text" => &mut theme.text,
+                "warning" => &mut theme.warning,
+                "selected" => &mut theme.selected,
+                "name" => {
+                    theme.name = value.to_owned();
+                    continue;
+                }
+                "block" => {
+                    block = if value == "atlas" || value == "flat" {
+                        value
+                    } else {
+                        "classic"
+                    };
+                    continue;
+                }
+                "atlas" => {
+                    atlas = load(value).and_then(|bytes| load_texture(&bytes));
+                    continue;
+                }
+                "ghost" => {
+                    if value == "translucent" {
+                        theme.ghost = GhostStyle::Translucent;
+                    }
+                    continue;
+                }
+                "background_image" => {
+                    theme.background_image = load(value).and_then(|bytes| load_texture(&bytes));
+                    continue;
+                }
+                "font" => {
+                    theme.font =
+                        load(value).and_then(|bytes| load_ttf_font_from_bytes(&bytes).ok());
+                    continue;
+                }
+                _ => continue,
+            };
+            *color = parse_color(value).unwrap_or(*color);
+        }
+
+        theme.block = match (block, atlas) {
+            ("atlas", Some(atlas)) => BlockStyle::Atlas(atlas),
+            ("flat", _) => BlockStyle::Flat,
+            _ => BlockStyle::Classic,
+        };
+        return Some(theme);
+    }
+
+    /// The themed color of a board cell or piece, given in its classic color.
+    pub fn color(&self, cell: (u8, u8, u8)) -> (u8, u8, u8) {
+        match self.tile(cell) {
+            Some(tile) if tile < self.pieces.len() => self.pieces[tile],
+            Some(_) => self.garbage,
+            None => self.board,
+        }
+    }
+
+    /// Index of the piece in `SHAPES`, or of the garbage tile after them, a cell of the
+    /// classic color `cell` is drawn with. Empty cells have none.
+    pub fn tile(&self, cell: (u8, u8, u8)) -> Option<usize> {
+        if cell == GARBAGE_COLOR {
+            return Some(self.pieces.len());
+        }
+        return SHAPES.iter().position(|shape| shape.color == cell);
+    }
+}
+
+/// Where tile `tile` sits in a block atlas `width` pixels wide.
+pub fn atlas_source(tile: usize, width: f32) -> Rect {
+    let size = width / ATLAS_TILES as f32;
+    return Rect::new(tile as f32 * size, 0.0, size, size);
+}
+
+/// Reads a color written in hexadecimal as `rrggbb`, with or without a leading `#`.
+fn parse_color(value: &str) -> Option<(u8, u8, u8)> {
+    let hex = value.trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    return Some((channel(0)?, channel(2)?, channel(4)?));
+}
+
+fn load_texture(bytes: &[u8]) -> Option<Texture2D> {
+    let image = Image::from_file_with_format(bytes, None).ok()?;
+    return Some(Texture2D::from_image(&image));
+}
+
+/// Every theme to choose from, the built-in ones first.
+pub struct Themes(Vec<Theme>);
+
+impl Themes {
+    /// Loads the built-in themes and those in the `themes` folder of the config directory,
+    /// where a theme named like a built-in one replaces it.
+    pub fn load() -> Self {
+        let mut themes = Vec::new();
+        for id in BUILT_IN {
+            let file = format!("themes/{}.txt", id);
+            let contents = Asset::get(&file).map(|file| file.data.into_owned());
+            let contents = String::from_utf8(contents.unwrap_or_default()).unwrap_or_default();
+            let load = |name: &str| {
+                let file = Asset::get(&format!("themes/{}", name))?;
+                return Some(file.data.into_owned());
+            };
+            themes.push(Theme::parse(id, &contents, load).unwrap_or_else(|| Theme::classic(id)));
+        }
+
+        let dir = storage::config_dir().map(|dir| dir.join("themes"));
+        let mut paths: Vec<_> = dir
+            .and_then(|dir| fs::read_dir(dir).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let (Some(id), Ok(contents)) = (path.file_stem(), fs::read_to_string(&path)) else {
+                continue;
+            };
+            let id = id.to_string_lossy();
+            let dir = path.parent().unwrap_or(Path::new("."));
+            let Some(theme) = Theme::parse(&id, &contents, |name| fs::read(dir.join(name)).ok())
+            else {
+                continue;
+            };
+            match themes.iter().position(|known| known.id == theme.id) {
+                Some(index) => themes[index] = theme,
+                None => themes.push(theme),
+            }
+        }
+        return Themes(themes);
+    }
+
+    /// The theme saved as `id`, or the classic one if it is gone.
+    pub fn get(&self, id: &str) -> &Theme {
+        return self
+            .0
+            .iter()
+            .find(|theme| theme.id == id)
+            .unwrap_or(&self.0[0]);
+    }
+
+    /// The id of the theme after or, unless `forward`, before the one saved as `id`.
+    pub fn cycle(&self, id: &str, forward: bool) -> String {
+        let count = self.0.len();
+        let index = self.0.iter().position(|theme| theme.id == id).unwrap_or(0);
+        let next = if forward {
+            (index + 1) % count
+        } else {
+            (index + count - 1) % count
+        };
+        return self.0[next].id.clone();
+    }
+}